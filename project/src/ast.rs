@@ -5,6 +5,113 @@ pub type Name = String;
 pub type Bast = Box<Ast>;
 pub type Vast = Vec<Ast>;
 
+/// position in the source, as reported by the lexer
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Span {
+    pub line : usize,
+    pub column : usize,
+}
+
+impl std::fmt::Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// a parameter of a function definition
+#[derive(Debug, Clone)]
+pub struct Param {
+    pub name : Name,
+    /// evaluated at call time, in the scope of the call, when the argument is missing
+    pub default : Option<Ast>,
+    /// `...name` collects the remaining positional arguments into a list
+    pub rest : bool,
+    pub span : Span,
+}
+
+impl std::fmt::Display for Param {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.default, self.rest) {
+            (_, true) => write!(f, "...{}", self.name),
+            (Some(_), _) => write!(f, "{} = ...", self.name),
+            (None, _) => write!(f, "{}", self.name),
+        }
+    }
+}
+
+/// the left hand side of a `match` arm
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    /// `_`
+    Wildcard,
+    /// a number or a string, numbers match across int and float
+    Literal(Value),
+    /// a name, matches anything and binds it
+    Bind(Name),
+    /// `[a, b, ...rest]`, without a rest the length must match exactly. `..._` ignores the rest
+    List { items : Vec<Pattern>, rest : Option<Name> },
+    /// `from..to`, a number with from <= n < to
+    Range { from : Value, to : Value },
+    /// `Name { field, field : pattern }`, the listed fields of a struct called Name
+    Struct { name : Name, fields : Vec<(Name, Pattern)> },
+}
+
+impl std::fmt::Display for Pattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Pattern::Wildcard => write!(f, "_"),
+            Pattern::Literal(Value::String(s)) => write!(f, "{:?}", s),
+            Pattern::Literal(v) => write!(f, "{}", v),
+            Pattern::Bind(name) => write!(f, "{}", name),
+            Pattern::List { items, rest } => {
+                let mut parts = items.iter().map(|p| format!("{}", p)).collect::<Vec<String>>();
+                if let Some(r) = rest {
+                    parts.push(format!("...{}", r));
+                }
+                write!(f, "[{}]", parts.join(", "))
+            },
+            Pattern::Range { from, to } => write!(f, "{}..{}", from, to),
+            Pattern::Struct { name, fields } => {
+                let parts = fields.iter().map(|(k, p)| match p {
+                    Pattern::Bind(n) if n == k => k.clone(),
+                    _ => format!("{} : {}", k, p),
+                }).collect::<Vec<String>>();
+                write!(f, "{} {{ {} }}", name, parts.join(", "))
+            },
+        }
+    }
+}
+
+impl Pattern {
+    /// every name the pattern binds, in order
+    pub fn names(&self) -> Vec<&Name> {
+        match self {
+            Pattern::Wildcard | Pattern::Literal(_) | Pattern::Range { .. } => vec![],
+            Pattern::Bind(name) => vec![name],
+            Pattern::List { items, rest } => {
+                let mut v : Vec<&Name> = items.iter().flat_map(|p| p.names()).collect();
+                v.extend(rest.as_ref());
+                v
+            },
+            Pattern::Struct { fields, .. } => fields.iter().flat_map(|(_, p)| p.names()).collect(),
+        }
+    }
+}
+
+fn defaults(params : &[Param]) -> Vec<&Ast> {
+    params.iter().filter_map(|p| p.default.as_ref()).collect()
+}
+
+/// `pattern if guard => body`
+#[derive(Debug, Clone)]
+pub struct Arm {
+    pub pattern : Pattern,
+    pub guard : Option<Ast>,
+    pub body : Ast,
+    /// of the `=>`
+    pub span : Span,
+}
+
 #[repr(u8)]
 #[derive(Debug, Copy, Clone, Hash)]
 pub enum Forkop {
@@ -20,20 +127,45 @@ pub enum Forkop {
     Div,
     Pow,
     Mod,
+    Union,
+    Intersect,
+    In,
 }
 
 #[derive(Debug, Clone)]
 pub enum Ast {
     Literal         (Value),
-    Get             (Name),
+    Get             (Name, Span),
     VecLiteral      (Vast),
-    FunDef          {name : Name, args : Vec<Name>, body : Bast},
-    Set             {name : Name, value : Bast},
+    /// `${a, b}`
+    SetLiteral      (Vast),
+    FunDef          {name : Name, args : Vec<Param>, body : Bast, span : Span},
+    Set             {name : Name, value : Bast, span : Span},
     While           {cond : Bast, body : Bast},
     ExpressionList  (Vast),
-    Call            {name : Name, with : Vast},
-    If              {if_ : Bast, then : Bast, else_ : Option<Bast>},
+    /// `with` is in reverse order, keyword arguments are in call order
+    Call            {name : Name, with : Vast, kwargs : Vec<(Name, Ast)>, span : Span},
+    /// `span` is the one of the `:`
+    If              {if_ : Bast, then : Bast, else_ : Option<Bast>, span : Span},
     Fork            {left : Bast, right : Bast, op : Forkop},
+    Throw           (Bast),
+    Try             {body : Bast, name : Name, handler : Bast},
+    Field           {target : Bast, field : Name},
+    Import          {path : String, alias : Option<Name>},
+    /// `struct Name { field, field = default }`
+    StructDef       {name : Name, fields : Vec<Param>, span : Span},
+    /// `target.field = value`, target is a variable or another field access
+    SetField        {target : Bast, field : Name, value : Bast},
+    /// `target[index]`
+    Index           {target : Bast, index : Bast},
+    /// `target[index] = value`
+    SetIndex        {target : Bast, index : Bast, value : Bast},
+    /// `target op= value`, target is a variable, a field or an index
+    Update          {target : Bast, op : Forkop, value : Bast},
+    /// the first arm whose pattern and guard match is evaluated
+    Match           {value : Bast, arms : Vec<Arm>},
+    /// `[a, ...rest] = value` or `Name { a, b } = value`, assigns every name the pattern binds
+    Destructure     {pattern : Pattern, value : Bast},
 }
 
 impl Ast {
@@ -46,4 +178,49 @@ impl Ast {
     pub fn pr(self) -> PR {
         PR::Ast(self)
     }
+
+    /// the position recorded for this node, only names, calls, assignments, definitions and conditionals have one
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Ast::Get(_, span) | Ast::Call { span, .. } | Ast::Set { span, .. } | Ast::FunDef { span, .. }
+            | Ast::StructDef { span, .. } | Ast::If { span, .. } => Some(*span),
+            _ => None,
+        }
+    }
+
+    /// the expressions directly inside this one, including parameter defaults and match guards
+    pub fn children(&self) -> Vec<&Ast> {
+        match self {
+            Ast::Literal(_) | Ast::Get(..) | Ast::Import { .. } => vec![],
+            Ast::VecLiteral(v) | Ast::SetLiteral(v) | Ast::ExpressionList(v) => v.iter().collect(),
+            Ast::FunDef { args, body, .. } => {
+                let mut v = defaults(args);
+                v.push(body);
+                v
+            },
+            Ast::StructDef { fields, .. } => defaults(fields),
+            Ast::Set { value, .. } | Ast::Destructure { value, .. } | Ast::Throw(value) => vec![value],
+            Ast::While { cond, body } => vec![cond, body],
+            Ast::Call { with, kwargs, .. } => with.iter().rev().chain(kwargs.iter().map(|(_, a)| a)).collect(),
+            Ast::If { if_, then, else_, .. } => {
+                let mut v : Vec<&Ast> = vec![if_, then];
+                v.extend(else_.as_deref());
+                v
+            },
+            Ast::Fork { left, right, .. } => vec![left, right],
+            Ast::Try { body, handler, .. } => vec![body, handler],
+            Ast::Field { target, .. } => vec![target],
+            Ast::SetField { target, value, .. } | Ast::Update { target, value, .. } => vec![target, value],
+            Ast::Index { target, index } => vec![target, index],
+            Ast::SetIndex { target, index, value } => vec![target, index, value],
+            Ast::Match { value, arms } => {
+                let mut v : Vec<&Ast> = vec![value];
+                for arm in arms {
+                    v.extend(arm.guard.as_ref());
+                    v.push(&arm.body);
+                }
+                v
+            },
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,22 @@
+#![allow(clippy::unused_unit)]
+
+use std::process::exit;
+
+use lsp_server::Connection;
+
+/// a language server for editors, speaking the protocol on standard input and output
+fn main() -> () {
+    let (connection, threads) = Connection::stdio();
+
+    if let Err(e) = project::lsp::serve(&connection) {
+        eprintln!("language server : {}", e);
+        exit(1);
+    }
+
+    // the writer thread finishes once the last message is sent
+    drop(connection);
+    if let Err(e) = threads.join() {
+        eprintln!("language server : {}", e);
+        exit(1);
+    }
+}
@@ -0,0 +1,914 @@
+use std::cmp::Ordering;
+
+use indexmap::IndexSet;
+use rug::{Float, Integer};
+use unicode_segmentation::UnicodeSegmentation;
+use regex::Captures;
+
+use super::environment::Environment;
+use super::value::{Value, FLOATING_PRECISION};
+use super::error::{ErrorKind, RuntimeError};
+
+/// a builtin implemented in rust, it gets the arguments in their natural order
+pub type Builtin = fn(&mut Environment, Vec<Value>) -> Result<Value, RuntimeError>;
+
+/// the list standard library, lists are values so the builtins return new lists
+pub const LIST : [(&str, Builtin); 23] = [
+    ("push", push),
+    ("insert", insert),
+    ("remove_at", remove_at),
+    ("concat", concat),
+    ("reverse", reverse),
+    ("sort", sort),
+    ("sort_by_key", sort_by_key),
+    ("map", map),
+    ("filter", filter),
+    ("reduce", reduce),
+    ("fold", fold),
+    ("any", any),
+    ("all", all),
+    ("find", find),
+    ("index_of", index_of),
+    ("zip", zip),
+    ("enumerate", enumerate),
+    ("flatten", flatten),
+    ("chunks", chunks),
+    ("windows", windows),
+    ("unique", unique),
+    ("sum", sum),
+    ("product", product),
+];
+
+/// the string standard library, lengths and positions count chars unless the name says graphemes
+pub const STRING : [(&str, Builtin); 22] = [
+    ("len", len),
+    ("grapheme_len", grapheme_len),
+    ("upper", upper),
+    ("lower", lower),
+    ("trim", trim),
+    ("trim_start", trim_start),
+    ("trim_end", trim_end),
+    ("split", split),
+    ("join", join),
+    ("replace", replace),
+    ("contains", contains),
+    ("starts_with", starts_with),
+    ("ends_with", ends_with),
+    ("repeat", repeat),
+    ("pad_left", pad_left),
+    ("pad_right", pad_right),
+    ("chars", chars),
+    ("graphemes", graphemes),
+    ("ord", ord),
+    ("chr", chr),
+    ("parse_int", parse_int),
+    ("parse_float", parse_float),
+];
+
+/// regular expressions, the pattern comes first. see https://docs.rs/regex for the syntax
+pub const REGEX : [(&str, Builtin); 5] = [
+    ("re_match", re_match),
+    ("re_find_all", re_find_all),
+    ("re_captures", re_captures),
+    ("re_replace", re_replace),
+    ("re_split", re_split),
+];
+
+/// the command line and the process environment
+pub const SYSTEM : [(&str, Builtin); 4] = [
+    ("args", args),
+    ("env", env),
+    ("set_env", set_env),
+    ("exit", exit),
+];
+
+fn tables() -> impl Iterator<Item = &'static (&'static str, Builtin)> {
+    LIST.iter().chain(STRING.iter()).chain(REGEX.iter()).chain(SYSTEM.iter())
+}
+
+pub fn get(name : &str) -> Option<Builtin> {
+    tables().find(|(n, _)| *n == name).map(|(_, f)| *f)
+}
+
+/// the names of every builtin, for completion and suggestions
+pub fn names() -> impl Iterator<Item = &'static str> {
+    tables().map(|(n, _)| *n)
+}
+
+/// the least and the most arguments a call to the builtin `name` takes, most is usize::MAX when there is no limit. \
+/// covers the builtins of Environment::eval_function as well, None when `name` is not a builtin
+pub fn arity_of(name : &str) -> Option<(usize, usize)> {
+    let a = match name {
+        "disp" | "displn" | "concat" => (0, usize::MAX),
+        "args" => (0, 0),
+        "exit" => (0, 1),
+        "dim" | "vec" | "str" | "int" | "nil" | "type_of" | "set"
+        | "reverse" | "enumerate" | "flatten" | "unique" | "sum" | "product"
+        | "len" | "grapheme_len" | "upper" | "lower" | "trim" | "trim_start" | "trim_end"
+        | "chars" | "graphemes" | "ord" | "chr" | "parse_int" | "parse_float" | "env" => (1, 1),
+        "sort" | "any" | "all" | "split" | "join" => (1, 2),
+        "pop" | "get" | "push" | "remove_at" | "sort_by_key" | "map" | "filter" | "find" | "index_of"
+        | "zip" | "chunks" | "windows" | "contains" | "starts_with" | "ends_with" | "repeat"
+        | "re_match" | "re_find_all" | "re_captures" | "re_split" | "set_env" => (2, 2),
+        "reduce" | "pad_left" | "pad_right" => (2, 3),
+        "insert" | "fold" | "replace" | "re_replace" => (3, 3),
+        _ => return None,
+    };
+
+    Some(a)
+}
+
+/// the signature and a one line description of the builtin `name`, for editors and help
+pub fn doc(name : &str) -> Option<&'static str> {
+    let d = match name {
+        "disp" => "disp(v, ...) writes the values without a newline",
+        "displn" => "displn(v, ...) writes each value on its own line",
+        "pop" => "pop(list, i) the list without the element at i",
+        "get" => "get(list, i) the element at i",
+        "dim" => "dim(v) the length of a string, list, set or struct, 0 for anything else",
+        "vec" => "vec(v) v converted to a list",
+        "str" => "str(v) v converted to a string",
+        "int" => "int(v) v converted to an integer",
+        "nil" => "nil(v) whether v is not nil",
+        "type_of" => "type_of(v) the name of the type of v",
+        "set" => "set(list) a set of the elements of list, in order of first occurrence",
+        "push" => "push(list, v) the list with v added at the end",
+        "insert" => "insert(list, i, v) the list with v at i, i may be the length of the list",
+        "remove_at" => "remove_at(list, i) the list without the element at i",
+        "concat" => "concat(a, b, ...) the lists or strings joined together",
+        "reverse" => "reverse(list) or reverse(string), strings by graphemes",
+        "sort" => "sort(list) or sort(list, cmp), cmp(a, b) returns a number below, equal to or above zero",
+        "sort_by_key" => "sort_by_key(list, f) sorted by f(element), f is called once per element",
+        "map" => "map(list, f) f applied to every element",
+        "filter" => "filter(list, f) the elements f accepts",
+        "reduce" => "reduce(list, f) starts from the first element, reduce(list, f, init) from init",
+        "fold" => "fold(list, init, f) f(acc, element) for every element starting from init",
+        "any" => "any(list) or any(list, f), stops at the first true",
+        "all" => "all(list) or all(list, f), stops at the first false",
+        "find" => "find(list, f) the first element f accepts or nil, find(string, sub) the char position of sub or nil",
+        "index_of" => "index_of(list, v) the index of the first element equal to v or nil",
+        "zip" => "zip(a, b) pairs up to the length of the shorter list",
+        "enumerate" => "enumerate(list) [index, element] pairs",
+        "flatten" => "flatten(list) one level deep",
+        "chunks" => "chunks(list, n) lists of n elements, the last one may be shorter",
+        "windows" => "windows(list, n) every run of n consecutive elements",
+        "unique" => "unique(list) the first occurrence of each element in order",
+        "sum" => "sum(list) the sum of the numbers",
+        "product" => "product(list) the product of the numbers",
+        "len" => "len(v) chars of a string or elements of a list or set",
+        "grapheme_len" => "grapheme_len(s) user perceived characters",
+        "upper" => "upper(s) s in upper case",
+        "lower" => "lower(s) s in lower case",
+        "trim" => "trim(s) s without whitespace around it",
+        "trim_start" => "trim_start(s) s without leading whitespace",
+        "trim_end" => "trim_end(s) s without trailing whitespace",
+        "split" => "split(s) on whitespace, split(s, sep) on sep",
+        "join" => "join(list, sep) the elements displayed like disp does, separated by sep",
+        "replace" => "replace(s, from, to) every occurrence of from replaced",
+        "contains" => "contains(s, sub), contains(list, v) or contains(set, v)",
+        "starts_with" => "starts_with(s, prefix)",
+        "ends_with" => "ends_with(s, suffix)",
+        "repeat" => "repeat(s, n) s n times",
+        "pad_left" => "pad_left(s, width, fill = \" \") s with fill in front up to width chars",
+        "pad_right" => "pad_right(s, width, fill = \" \") s with fill after it up to width chars",
+        "chars" => "chars(s) a list of one char strings",
+        "graphemes" => "graphemes(s) a list of user perceived characters",
+        "ord" => "ord(c) the code point of a one char string",
+        "chr" => "chr(n) the one char string of a code point",
+        "parse_int" => "parse_int(s) surrounding whitespace is ignored",
+        "parse_float" => "parse_float(s) surrounding whitespace is ignored",
+        "re_match" => "re_match(pattern, s) the groups of the first match or nil",
+        "re_find_all" => "re_find_all(pattern, s) every non overlapping match",
+        "re_captures" => "re_captures(pattern, s) the groups of every match",
+        "re_replace" => "re_replace(pattern, s, with) every match replaced, `$1` and `${name}` in with refer to groups",
+        "re_split" => "re_split(pattern, s) the parts between the matches",
+        "args" => "args() the command line arguments after the script",
+        "env" => "env(name) the environment variable, nil when it is unset or not unicode",
+        "set_env" => "set_env(name, value) for the script and the programs it starts",
+        "exit" => "exit(code = 0) stops the program",
+        _ => return None,
+    };
+
+    Some(d)
+}
+
+fn arity(name : &str, args : &[Value], min : usize, max : usize) -> Result<(), RuntimeError> {
+    if args.len() < min || args.len() > max {
+        let expected = if min == max {format!("{}", min)} else {format!("{} to {}", min, max)};
+        return Err(RuntimeError::new(ErrorKind::Arity, format!("Calls to {} should have {} arguments, found {}", name, expected, args.len())));
+    }
+
+    Ok(())
+}
+
+fn list(name : &str, v : Value) -> Result<Vec<Value>, RuntimeError> {
+    match v {
+        Value::List(l) => Ok(l),
+        v => Err(RuntimeError::new(ErrorKind::Type, format!("{} expects a list, found {}", name, v.type_name()))),
+    }
+}
+
+fn index(name : &str, v : &Value) -> Result<usize, RuntimeError> {
+    match v {
+        Value::Integer(i) => i.to_usize().ok_or_else(|| RuntimeError::new(ErrorKind::Index, format!("{} called with out of bounds index {}", name, i))),
+        v => Err(RuntimeError::new(ErrorKind::Type, format!("{} expects an integer index, found {}", name, v.type_name()))),
+    }
+}
+
+/// a positive size, for chunks and windows
+fn size(name : &str, v : &Value) -> Result<usize, RuntimeError> {
+    match v {
+        Value::Integer(i) if *i > 0 => i.to_usize().ok_or_else(|| RuntimeError::new(ErrorKind::Index, format!("{} called with a size too large", name))),
+        v => Err(RuntimeError::new(ErrorKind::Type, format!("{} expects a positive integer size, found {}", name, v))),
+    }
+}
+
+fn truthy(v : Value) -> Result<bool, RuntimeError> {
+    v.force_bool()
+}
+
+/// push(list, v)
+fn push(_ : &mut Environment, args : Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("push", &args, 2, 2)?;
+    let [l, v] = <[Value; 2]>::try_from(args).unwrap();
+
+    let mut l = list("push", l)?;
+    l.push(v);
+    Ok(Value::List(l))
+}
+
+/// insert(list, i, v), i may be the length of the list
+fn insert(_ : &mut Environment, args : Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("insert", &args, 3, 3)?;
+    let [l, i, v] = <[Value; 3]>::try_from(args).unwrap();
+
+    let mut l = list("insert", l)?;
+    let i = index("insert", &i)?;
+    if i > l.len() {
+        return Err(RuntimeError::new(ErrorKind::Index, format!("insert called with out of bounds index {}", i)));
+    }
+
+    l.insert(i, v);
+    Ok(Value::List(l))
+}
+
+/// remove_at(list, i)
+fn remove_at(_ : &mut Environment, args : Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("remove_at", &args, 2, 2)?;
+    let [l, i] = <[Value; 2]>::try_from(args).unwrap();
+
+    let mut l = list("remove_at", l)?;
+    let i = index("remove_at", &i)?;
+    if i >= l.len() {
+        return Err(RuntimeError::new(ErrorKind::Index, format!("remove_at called with out of bounds index {}", i)));
+    }
+
+    l.remove(i);
+    Ok(Value::List(l))
+}
+
+/// concat(a, b, ...)
+fn concat(env : &mut Environment, args : Vec<Value>) -> Result<Value, RuntimeError> {
+    let lists = args.into_iter().map(|l| list("concat", l)).collect::<Result<Vec<Vec<Value>>, RuntimeError>>()?;
+    reserve(env, lists.iter().map(|l| l.len()).sum())?;
+
+    Ok(Value::List(lists.into_iter().flatten().collect()))
+}
+
+/// reverse(list), or reverse(string) by graphemes so combining marks stay attached
+fn reverse(_ : &mut Environment, args : Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("reverse", &args, 1, 1)?;
+    let [l] = <[Value; 1]>::try_from(args).unwrap();
+
+    if let Value::String(s) = &l {
+        return Ok(Value::String(s.graphemes(true).rev().collect()));
+    }
+
+    let mut l = list("reverse", l)?;
+    l.reverse();
+    Ok(Value::List(l))
+}
+
+/// a stable merge sort with a fallible comparison, the first error is returned. \
+/// cmp does not have to be a total order, if it is not the elements just end up in some order
+fn sort_with<T>(l : Vec<T>, cmp : &mut impl FnMut(&T, &T) -> Result<Ordering, RuntimeError>) -> Result<Vec<T>, RuntimeError> {
+    if l.len() <= 1 {
+        return Ok(l);
+    }
+
+    let mut left = l;
+    let right = left.split_off(left.len() / 2);
+    let mut left = sort_with(left, cmp)?.into_iter().peekable();
+    let mut right = sort_with(right, cmp)?.into_iter().peekable();
+
+    let mut ret = Vec::with_capacity(left.len() + right.len());
+    while let (Some(a), Some(b)) = (left.peek(), right.peek()) {
+        // the left one first unless it is greater, equal elements keep their order
+        let next = if cmp(b, a)? == Ordering::Less { right.next() } else { left.next() };
+        ret.extend(next);
+    }
+    ret.extend(left);
+    ret.extend(right);
+
+    Ok(ret)
+}
+
+fn natural_order(a : &Value, b : &Value) -> Result<Ordering, RuntimeError> {
+    a.partial_cmp(b).ok_or_else(|| RuntimeError::new(ErrorKind::Type, format!("cannot order {} and {}", a.type_name(), b.type_name())))
+}
+
+/// sort(list) or sort(list, cmp), cmp(a, b) returns a number below, equal to or above zero
+fn sort(env : &mut Environment, args : Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("sort", &args, 1, 2)?;
+    let mut args = args.into_iter();
+
+    let l = list("sort", args.next().unwrap())?;
+
+    let l = match args.next() {
+        None => sort_with(l, &mut natural_order)?,
+        Some(f) => sort_with(l, &mut |a, b| {
+            let r = env.call_value(&f, vec![a.clone(), b.clone()])?;
+            natural_order(&r, &Value::Integer(Integer::new()))
+        })?,
+    };
+
+    Ok(Value::List(l))
+}
+
+/// sort_by_key(list, f), f is called once per element
+fn sort_by_key(env : &mut Environment, args : Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("sort_by_key", &args, 2, 2)?;
+    let [l, f] = <[Value; 2]>::try_from(args).unwrap();
+
+    let mut keyed = Vec::new();
+    for v in list("sort_by_key", l)? {
+        keyed.push((env.call_value(&f, vec![v.clone()])?, v));
+    }
+
+    let keyed = sort_with(keyed, &mut |a, b| natural_order(&a.0, &b.0))?;
+    Ok(Value::List(keyed.into_iter().map(|(_, v)| v).collect()))
+}
+
+/// map(list, f)
+fn map(env : &mut Environment, args : Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("map", &args, 2, 2)?;
+    let [l, f] = <[Value; 2]>::try_from(args).unwrap();
+
+    let mut ret = Vec::new();
+    for v in list("map", l)? {
+        ret.push(env.call_value(&f, vec![v])?);
+    }
+
+    Ok(Value::List(ret))
+}
+
+/// filter(list, f)
+fn filter(env : &mut Environment, args : Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("filter", &args, 2, 2)?;
+    let [l, f] = <[Value; 2]>::try_from(args).unwrap();
+
+    let mut ret = Vec::new();
+    for v in list("filter", l)? {
+        if truthy(env.call_value(&f, vec![v.clone()])?)? {
+            ret.push(v);
+        }
+    }
+
+    Ok(Value::List(ret))
+}
+
+/// reduce(list, f) starts from the first element, reduce(list, f, init) from init
+fn reduce(env : &mut Environment, args : Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("reduce", &args, 2, 3)?;
+    let mut args = args.into_iter();
+
+    let mut l = list("reduce", args.next().unwrap())?.into_iter();
+    let f = args.next().unwrap();
+
+    let Some(mut acc) = args.next().or_else(|| l.next()) else {
+        return Err(RuntimeError::new(ErrorKind::Index, format!("reduce of an empty list without an initial value")));
+    };
+
+    for v in l {
+        acc = env.call_value(&f, vec![acc, v])?;
+    }
+
+    Ok(acc)
+}
+
+/// fold(list, init, f)
+fn fold(env : &mut Environment, args : Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("fold", &args, 3, 3)?;
+    let [l, init, f] = <[Value; 3]>::try_from(args).unwrap();
+
+    let mut acc = init;
+    for v in list("fold", l)? {
+        acc = env.call_value(&f, vec![acc, v])?;
+    }
+
+    Ok(acc)
+}
+
+/// any(list) or any(list, f), stops at the first true
+fn any(env : &mut Environment, args : Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("any", &args, 1, 2)?;
+    let mut args = args.into_iter();
+    let l = list("any", args.next().unwrap())?;
+    let f = args.next();
+
+    for v in l {
+        let t = match &f {
+            Some(f) => env.call_value(f, vec![v])?,
+            None => v,
+        };
+
+        if truthy(t)? {
+            return Ok(Value::Bool(true));
+        }
+    }
+
+    Ok(Value::Bool(false))
+}
+
+/// all(list) or all(list, f), stops at the first false
+fn all(env : &mut Environment, args : Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("all", &args, 1, 2)?;
+    let mut args = args.into_iter();
+    let l = list("all", args.next().unwrap())?;
+    let f = args.next();
+
+    for v in l {
+        let t = match &f {
+            Some(f) => env.call_value(f, vec![v])?,
+            None => v,
+        };
+
+        if !truthy(t)? {
+            return Ok(Value::Bool(false));
+        }
+    }
+
+    Ok(Value::Bool(true))
+}
+
+/// find(list, f), the first element f accepts or nil. \
+/// find(string, sub), the char position of sub or nil
+fn find(env : &mut Environment, args : Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("find", &args, 2, 2)?;
+    let [l, f] = <[Value; 2]>::try_from(args).unwrap();
+
+    if let Value::String(s) = &l {
+        let sub = string("find", f)?;
+        return Ok(s.find(sub.as_str()).map(|b| s[..b].chars().count()).into());
+    }
+
+    for v in list("find", l)? {
+        if truthy(env.call_value(&f, vec![v.clone()])?)? {
+            return Ok(v);
+        }
+    }
+
+    Ok(Value::Nil)
+}
+
+/// index_of(list, v), the index of the first element equal to v or nil
+fn index_of(_ : &mut Environment, args : Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("index_of", &args, 2, 2)?;
+    let [l, v] = <[Value; 2]>::try_from(args).unwrap();
+
+    Ok(list("index_of", l)?.iter().position(|x| *x == v).into())
+}
+
+/// zip(a, b), pairs up to the length of the shorter list
+fn zip(_ : &mut Environment, args : Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("zip", &args, 2, 2)?;
+    let [a, b] = <[Value; 2]>::try_from(args).unwrap();
+
+    let pairs = list("zip", a)?.into_iter().zip(list("zip", b)?).map(|(x, y)| Value::List(vec![x, y]));
+    Ok(Value::List(pairs.collect()))
+}
+
+/// enumerate(list), [index, element] pairs
+fn enumerate(_ : &mut Environment, args : Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("enumerate", &args, 1, 1)?;
+    let [l] = <[Value; 1]>::try_from(args).unwrap();
+
+    let pairs = list("enumerate", l)?.into_iter().enumerate().map(|(i, x)| Value::List(vec![i.into(), x]));
+    Ok(Value::List(pairs.collect()))
+}
+
+/// flatten(list), one level deep
+fn flatten(env : &mut Environment, args : Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("flatten", &args, 1, 1)?;
+    let [l] = <[Value; 1]>::try_from(args).unwrap();
+
+    let l = list("flatten", l)?;
+    reserve(env, l.iter().map(|v| match v { Value::List(inner) => inner.len(), _ => 1 }).sum())?;
+
+    let mut ret = Vec::new();
+    for v in l {
+        match v {
+            Value::List(inner) => ret.extend(inner),
+            v => ret.push(v),
+        }
+    }
+
+    Ok(Value::List(ret))
+}
+
+/// chunks(list, n), the last chunk may be shorter
+fn chunks(env : &mut Environment, args : Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("chunks", &args, 2, 2)?;
+    let [l, n] = <[Value; 2]>::try_from(args).unwrap();
+
+    let n = size("chunks", &n)?;
+    let l = list("chunks", l)?;
+    reserve(env, l.len().div_ceil(n))?;
+
+    Ok(Value::List(l.chunks(n).map(|c| Value::List(c.to_vec())).collect()))
+}
+
+/// windows(list, n), every run of n consecutive elements
+fn windows(env : &mut Environment, args : Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("windows", &args, 2, 2)?;
+    let [l, n] = <[Value; 2]>::try_from(args).unwrap();
+
+    let n = size("windows", &n)?;
+    let l = list("windows", l)?;
+    reserve(env, (l.len() + 1).saturating_sub(n))?;
+
+    Ok(Value::List(l.windows(n).map(|c| Value::List(c.to_vec())).collect()))
+}
+
+/// unique(list), the first occurrence of each element in order
+fn unique(_ : &mut Environment, args : Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("unique", &args, 1, 1)?;
+    let [l] = <[Value; 1]>::try_from(args).unwrap();
+
+    let Value::Set(s) = Value::set_of(list("unique", l)?)? else {unreachable!()};
+    Ok(Value::List(IndexSet::into_iter(*s).collect()))
+}
+
+/// folds numbers with op, integers stay exact unless a float is involved
+fn numeric_fold(name : &str, args : Vec<Value>, start : i32, op : fn(Value, Value) -> Result<Value, RuntimeError>) -> Result<Value, RuntimeError> {
+    arity(name, &args, 1, 1)?;
+    let [l] = <[Value; 1]>::try_from(args).unwrap();
+
+    let mut acc = Value::Integer(Integer::from(start));
+    for v in list(name, l)? {
+        if !v.is_comparable() {
+            return Err(RuntimeError::new(ErrorKind::Type, format!("{} expects a list of numbers, found {}", name, v.type_name())));
+        }
+        acc = op(acc, v)?;
+    }
+
+    Ok(acc)
+}
+
+/// sum(list)
+fn sum(_ : &mut Environment, args : Vec<Value>) -> Result<Value, RuntimeError> {
+    numeric_fold("sum", args, 0, Value::add)
+}
+
+/// product(list)
+fn product(_ : &mut Environment, args : Vec<Value>) -> Result<Value, RuntimeError> {
+    numeric_fold("product", args, 1, Value::mul)
+}
+
+fn string(name : &str, v : Value) -> Result<String, RuntimeError> {
+    match v {
+        Value::String(s) => Ok(s),
+        v => Err(RuntimeError::new(ErrorKind::Type, format!("{} expects a string, found {}", name, v.type_name()))),
+    }
+}
+
+/// a builtin taking one string
+fn unary(name : &str, args : Vec<Value>) -> Result<String, RuntimeError> {
+    arity(name, &args, 1, 1)?;
+    let [s] = <[Value; 1]>::try_from(args).unwrap();
+    string(name, s)
+}
+
+/// a builtin taking two strings
+fn binary(name : &str, args : Vec<Value>) -> Result<(String, String), RuntimeError> {
+    arity(name, &args, 2, 2)?;
+    let [a, b] = <[Value; 2]>::try_from(args).unwrap();
+    Ok((string(name, a)?, string(name, b)?))
+}
+
+/// len(v), chars of a string or elements of a list or set
+fn len(_ : &mut Environment, args : Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("len", &args, 1, 1)?;
+
+    match &args[0] {
+        Value::String(s) => Ok(s.chars().count().into()),
+        Value::List(l) => Ok(l.len().into()),
+        Value::Set(s) => Ok(s.len().into()),
+        v => Err(RuntimeError::new(ErrorKind::Type, format!("len expects a string, list or set, found {}", v.type_name()))),
+    }
+}
+
+/// grapheme_len(s), user perceived characters
+fn grapheme_len(_ : &mut Environment, args : Vec<Value>) -> Result<Value, RuntimeError> {
+    Ok(unary("grapheme_len", args)?.graphemes(true).count().into())
+}
+
+fn upper(_ : &mut Environment, args : Vec<Value>) -> Result<Value, RuntimeError> {
+    Ok(unary("upper", args)?.to_uppercase().into())
+}
+
+fn lower(_ : &mut Environment, args : Vec<Value>) -> Result<Value, RuntimeError> {
+    Ok(unary("lower", args)?.to_lowercase().into())
+}
+
+fn trim(_ : &mut Environment, args : Vec<Value>) -> Result<Value, RuntimeError> {
+    Ok(unary("trim", args)?.trim().into())
+}
+
+fn trim_start(_ : &mut Environment, args : Vec<Value>) -> Result<Value, RuntimeError> {
+    Ok(unary("trim_start", args)?.trim_start().into())
+}
+
+fn trim_end(_ : &mut Environment, args : Vec<Value>) -> Result<Value, RuntimeError> {
+    Ok(unary("trim_end", args)?.trim_end().into())
+}
+
+/// split(s) on whitespace, split(s, sep) on sep
+fn split(_ : &mut Environment, args : Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("split", &args, 1, 2)?;
+    let mut args = args.into_iter();
+    let s = string("split", args.next().unwrap())?;
+
+    let parts : Vec<&str> = match args.next() {
+        None => s.split_whitespace().collect(),
+        Some(sep) => {
+            let sep = string("split", sep)?;
+            if sep.is_empty() {
+                return Err(RuntimeError::new(ErrorKind::Type, format!("split separator can not be empty")));
+            }
+            s.split(sep.as_str()).collect()
+        },
+    };
+
+    Ok(parts.into())
+}
+
+/// join(list, sep), elements are displayed like disp does
+fn join(_ : &mut Environment, args : Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("join", &args, 1, 2)?;
+    let mut args = args.into_iter();
+    let l = list("join", args.next().unwrap())?;
+    let sep = match args.next() {
+        Some(sep) => string("join", sep)?,
+        None => String::new(),
+    };
+
+    Ok(l.iter().map(|v| format!("{}", v)).collect::<Vec<String>>().join(&sep).into())
+}
+
+/// replace(s, from, to), every occurrence
+fn replace(_ : &mut Environment, args : Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("replace", &args, 3, 3)?;
+    let [s, from, to] = <[Value; 3]>::try_from(args).unwrap();
+    let (s, from, to) = (string("replace", s)?, string("replace", from)?, string("replace", to)?);
+
+    if from.is_empty() {
+        return Err(RuntimeError::new(ErrorKind::Type, format!("replace pattern can not be empty")));
+    }
+
+    Ok(s.replace(from.as_str(), &to).into())
+}
+
+/// contains(s, sub), contains(list, v) or contains(set, v)
+fn contains(_ : &mut Environment, args : Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("contains", &args, 2, 2)?;
+    Ok(args[0].contains(&args[1])?.into())
+}
+
+fn starts_with(_ : &mut Environment, args : Vec<Value>) -> Result<Value, RuntimeError> {
+    let (s, p) = binary("starts_with", args)?;
+    Ok(s.starts_with(p.as_str()).into())
+}
+
+fn ends_with(_ : &mut Environment, args : Vec<Value>) -> Result<Value, RuntimeError> {
+    let (s, p) = binary("ends_with", args)?;
+    Ok(s.ends_with(p.as_str()).into())
+}
+
+/// repeat(s, n)
+fn repeat(env : &mut Environment, args : Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("repeat", &args, 2, 2)?;
+    let [s, n] = <[Value; 2]>::try_from(args).unwrap();
+    let s = string("repeat", s)?;
+    let n = index("repeat", &n)?;
+
+    reserve(env, s.len().saturating_mul(n))?;
+    Ok(s.repeat(n).into())
+}
+
+/// the size limit for a list of `size` elements or a string of `size` bytes, checked before building it since a huge one would exhaust memory first
+fn reserve(env : &Environment, size : usize) -> Result<(), RuntimeError> {
+    if let Some(max) = env.limits.max_size {
+        if size > max {
+            return Err(RuntimeError::new(ErrorKind::MemoryLimit, format!("value of size {} exceeds the limit of {}", size, max)));
+        }
+    }
+
+    Ok(())
+}
+
+/// pad(s, width, fill) with fill defaulting to a space, the width counts chars
+fn pad(env : &mut Environment, name : &str, args : Vec<Value>, left : bool) -> Result<Value, RuntimeError> {
+    arity(name, &args, 2, 3)?;
+    let mut args = args.into_iter();
+    let s = string(name, args.next().unwrap())?;
+    let width = index(name, &args.next().unwrap())?;
+    let fill = match args.next() {
+        Some(f) => string(name, f)?,
+        None => String::from(" "),
+    };
+
+    let mut fill = fill.chars();
+    let (Some(c), None) = (fill.next(), fill.next()) else {
+        return Err(RuntimeError::new(ErrorKind::Type, format!("{} expects a single character to pad with", name)));
+    };
+
+    let missing = width.saturating_sub(s.chars().count());
+    reserve(env, c.len_utf8().saturating_mul(missing).saturating_add(s.len()))?;
+
+    let padding = c.to_string().repeat(missing);
+    Ok(if left {padding + &s} else {s + &padding}.into())
+}
+
+fn pad_left(env : &mut Environment, args : Vec<Value>) -> Result<Value, RuntimeError> {
+    pad(env, "pad_left", args, true)
+}
+
+fn pad_right(env : &mut Environment, args : Vec<Value>) -> Result<Value, RuntimeError> {
+    pad(env, "pad_right", args, false)
+}
+
+/// chars(s), a list of one char strings
+fn chars(_ : &mut Environment, args : Vec<Value>) -> Result<Value, RuntimeError> {
+    Ok(unary("chars", args)?.chars().map(String::from).collect::<Vec<String>>().into())
+}
+
+/// graphemes(s), a list of user perceived characters
+fn graphemes(_ : &mut Environment, args : Vec<Value>) -> Result<Value, RuntimeError> {
+    Ok(unary("graphemes", args)?.graphemes(true).collect::<Vec<&str>>().into())
+}
+
+/// ord(c), the code point of a one char string
+fn ord(_ : &mut Environment, args : Vec<Value>) -> Result<Value, RuntimeError> {
+    let s = unary("ord", args)?;
+    let mut c = s.chars();
+
+    match (c.next(), c.next()) {
+        (Some(c), None) => Ok(Value::from(c as u32)),
+        _ => Err(RuntimeError::new(ErrorKind::Type, format!("ord expects a single character, found {:?}", s))),
+    }
+}
+
+/// chr(n), the one char string of a code point
+fn chr(_ : &mut Environment, args : Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("chr", &args, 1, 1)?;
+
+    match &args[0] {
+        Value::Integer(i) => match i.to_u32().and_then(char::from_u32) {
+            Some(c) => Ok(c.to_string().into()),
+            None => Err(RuntimeError::new(ErrorKind::Type, format!("{} is not a valid code point", i))),
+        },
+        v => Err(RuntimeError::new(ErrorKind::Type, format!("chr expects an integer, found {}", v.type_name()))),
+    }
+}
+
+/// parse_int(s), surrounding whitespace is ignored
+fn parse_int(_ : &mut Environment, args : Vec<Value>) -> Result<Value, RuntimeError> {
+    let s = unary("parse_int", args)?;
+
+    match Integer::from_str_radix(s.trim(), 10) {
+        Ok(i) => Ok(Value::Integer(i)),
+        Err(_) => Err(RuntimeError::new(ErrorKind::Parse, format!("cannot parse {:?} as an integer", s))),
+    }
+}
+
+/// parse_float(s), surrounding whitespace is ignored
+fn parse_float(_ : &mut Environment, args : Vec<Value>) -> Result<Value, RuntimeError> {
+    let s = unary("parse_float", args)?;
+
+    match Float::parse(s.trim()) {
+        Ok(f) => Ok(Value::Float(Float::with_val(FLOATING_PRECISION, f))),
+        Err(_) => Err(RuntimeError::new(ErrorKind::Parse, format!("cannot parse {:?} as a float", s))),
+    }
+}
+
+/// the groups of one match, the whole match first and nil for a group that did not take part
+fn groups(c : &Captures) -> Value {
+    Value::List(c.iter().map(|m| m.map(|m| m.as_str()).into()).collect())
+}
+
+/// re_match(pattern, s), the groups of the first match or nil
+fn re_match(env : &mut Environment, args : Vec<Value>) -> Result<Value, RuntimeError> {
+    let (p, s) = binary("re_match", args)?;
+
+    Ok(match env.regex(&p)?.captures(&s) {
+        Some(c) => groups(&c),
+        None => Value::Nil,
+    })
+}
+
+/// re_find_all(pattern, s), every non overlapping match
+fn re_find_all(env : &mut Environment, args : Vec<Value>) -> Result<Value, RuntimeError> {
+    let (p, s) = binary("re_find_all", args)?;
+    Ok(env.regex(&p)?.find_iter(&s).map(|m| m.as_str()).collect::<Vec<&str>>().into())
+}
+
+/// re_captures(pattern, s), the groups of every match
+fn re_captures(env : &mut Environment, args : Vec<Value>) -> Result<Value, RuntimeError> {
+    let (p, s) = binary("re_captures", args)?;
+    Ok(Value::List(env.regex(&p)?.captures_iter(&s).map(|c| groups(&c)).collect()))
+}
+
+/// re_replace(pattern, s, with), every match, `$1` and `${name}` in with refer to groups
+fn re_replace(env : &mut Environment, args : Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("re_replace", &args, 3, 3)?;
+    let [p, s, with] = <[Value; 3]>::try_from(args).unwrap();
+    let (p, s, with) = (string("re_replace", p)?, string("re_replace", s)?, string("re_replace", with)?);
+
+    Ok(env.regex(&p)?.replace_all(&s, with.as_str()).into_owned().into())
+}
+
+/// re_split(pattern, s)
+fn re_split(env : &mut Environment, args : Vec<Value>) -> Result<Value, RuntimeError> {
+    let (p, s) = binary("re_split", args)?;
+    Ok(env.regex(&p)?.split(&s).collect::<Vec<&str>>().into())
+}
+
+/// args(), the command line arguments after the script
+fn args(env : &mut Environment, args : Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("args", &args, 0, 0)?;
+    Ok(env.args.clone().into())
+}
+
+/// env(name), nil when the variable is unset or not unicode
+fn env(_ : &mut Environment, args : Vec<Value>) -> Result<Value, RuntimeError> {
+    let name = unary("env", args)?;
+    Ok(match std::env::var(&name) {
+        Ok(v) => Value::String(v),
+        Err(_) => Value::Nil,
+    })
+}
+
+/// set_env(name, value), for the script and the programs it starts
+fn set_env(_ : &mut Environment, args : Vec<Value>) -> Result<Value, RuntimeError> {
+    let (name, value) = binary("set_env", args)?;
+
+    if name.is_empty() || name.contains('=') || name.contains('\0') || value.contains('\0') {
+        return Err(RuntimeError::new(ErrorKind::Type, format!("set_env can not set {:?} to {:?}", name, value)));
+    }
+
+    std::env::set_var(name, value);
+    Ok(Value::Nil)
+}
+
+/// exit(code = 0), stops the program. the host decides what that means, the binary exits the process
+fn exit(_ : &mut Environment, args : Vec<Value>) -> Result<Value, RuntimeError> {
+    arity("exit", &args, 0, 1)?;
+
+    let code = match args.first() {
+        None => 0,
+        Some(Value::Integer(i)) => match i.to_i32() {
+            Some(c) => c,
+            None => return Err(RuntimeError::new(ErrorKind::Type, format!("exit status {} is out of range", i))),
+        },
+        Some(v) => return Err(RuntimeError::new(ErrorKind::Type, format!("exit expects an integer, found {}", v.type_name()))),
+    };
+
+    Err(RuntimeError::exit(code))
+}
+
+#[test]
+fn test_arity_of() -> () {
+    use super::environment::BUILTINS;
+
+    let mut env = Environment::new();
+    let nils = |n : usize| (0..n).map(|_| Value::Nil).collect::<Vec<Value>>();
+
+    for name in names().chain(BUILTINS.iter().copied()) {
+        let Some((min, max)) = arity_of(name) else {
+            panic!("no arity for {}", name);
+        };
+        assert!(doc(name).is_some_and(|d| d.starts_with(&format!("{}(", name))), "no doc for {}", name);
+
+        // eval_function wants the arguments reversed, which does not matter for nils
+        let mut call = |n : usize| env.eval_function(&String::from(name), nils(n), vec![], Default::default()).err().map(|e| e.kind);
+
+        if min > 0 {
+            assert_eq!(call(min - 1), Some(ErrorKind::Arity), "{} with {} arguments", name, min - 1);
+        }
+        if max != usize::MAX {
+            assert_eq!(call(max + 1), Some(ErrorKind::Arity), "{} with {} arguments", name, max + 1);
+        }
+        assert_ne!(call(min), Some(ErrorKind::Arity), "{} with {} arguments", name, min);
+    }
+}
@@ -0,0 +1,486 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use super::ast::{Ast, Param, Pattern, Span, Vast};
+use super::builtins;
+use super::environment::{is_builtin, Environment};
+use super::module::module_name;
+use super::value::Value;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// not reported
+    Allow,
+    Warning,
+    /// the program would fail, `check` exits with an error
+    Error,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Allow => write!(f, "allow"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// every lint with its default severity and what it reports
+pub const LINTS : [(&str, Severity, &str); 9] = [
+    ("undefined-function", Severity::Error, "a call to a function that is never defined"),
+    ("unbound-variable", Severity::Error, "a variable that is read but never assigned"),
+    ("arity", Severity::Error, "a call with too few or too many arguments"),
+    ("invalid-parameters", Severity::Error, "duplicate parameters, or a rest parameter that is not the last one"),
+    ("redefined-builtin", Severity::Error, "a function or struct with the name of a builtin"),
+    ("unreachable", Severity::Warning, "a conditional branch or a match arm that is never taken"),
+    ("unused-variable", Severity::Warning, "a variable of a function that is assigned but never read"),
+    ("shadowed-parameter", Severity::Warning, "a parameter that a binding overwrites or that hides a function"),
+    ("assignment-in-condition", Severity::Warning, "a condition that assigns, where == was probably meant"),
+];
+
+/// the severity of every lint
+#[derive(Debug, Clone)]
+pub struct Lints {
+    levels : HashMap<&'static str, Severity>,
+}
+
+impl Default for Lints {
+    fn default() -> Lints {
+        Lints::new()
+    }
+}
+
+impl Lints {
+    pub fn new() -> Lints {
+        Lints { levels : LINTS.iter().map(|(name, s, _)| (*name, *s)).collect() }
+    }
+
+    /// Err for a lint that does not exist
+    pub fn set(&mut self, lint : &str, severity : Severity) -> Result<(), String> {
+        match LINTS.iter().find(|(name, _, _)| *name == lint) {
+            Some((name, _, _)) => {
+                self.levels.insert(name, severity);
+                Ok(())
+            },
+            None => Err(format!("there is no lint called {}", lint)),
+        }
+    }
+
+    pub fn severity(&self, lint : &str) -> Severity {
+        self.levels.get(lint).copied().unwrap_or(Severity::Allow)
+    }
+}
+
+/// something wrong with a program that can be found without running it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Problem {
+    pub lint : &'static str,
+    pub severity : Severity,
+    /// where it is, if the Ast records it
+    pub span : Option<Span>,
+    pub message : String,
+}
+
+impl std::fmt::Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(s) = self.span {
+            write!(f, "{} : ", s)?;
+        }
+        write!(f, "{} : {} [{}]", self.severity, self.message, self.lint)
+    }
+}
+
+/// parses `text` and checks it. a `// lint: allow(name, ...)` comment suppresses those lints on its line,
+/// or on the next line when it is the only thing on its line. `all` suppresses every lint
+pub fn lint(text : &str, env : &Environment, lints : &Lints) -> Result<Vec<Problem>, String> {
+    let tree = env.syntax.parse(text)?;
+    let allowed = suppressions(text);
+
+    let mut problems = check(&tree, env, lints);
+    problems.retain(|p| {
+        let Some(span) = p.span else {
+            return true;
+        };
+        match allowed.get(&span.line) {
+            Some(names) => !names.iter().any(|n| n == p.lint || n == "all"),
+            None => true,
+        }
+    });
+
+    Ok(problems)
+}
+
+/// the lints allowed by comments, by line
+fn suppressions(text : &str) -> HashMap<usize, Vec<String>> {
+    let mut allowed : HashMap<usize, Vec<String>> = HashMap::new();
+
+    for (n, before, comment) in line_comments(text) {
+        let Some(rest) = comment.trim_start().strip_prefix("lint:") else {
+            continue;
+        };
+        let Some(names) = rest.trim_start().strip_prefix("allow(").and_then(|r| r.split_once(')')) else {
+            continue;
+        };
+
+        let target = if before.trim().is_empty() { n + 2 } else { n + 1 };
+        allowed.entry(target).or_default().extend(names.0.split(',').map(|s| String::from(s.trim())));
+    }
+
+    allowed
+}
+
+/// the `//` comments as (line index, the line before the comment, the comment after the slashes). \
+/// strings and block comments are skipped the way the lexer does, a `//` inside them is not a comment
+fn line_comments(text : &str) -> Vec<(usize, &str, &str)> {
+    let mut found = Vec::new();
+    let (mut line, mut line_start, mut i) = (0, 0, 0);
+
+    while i < text.len() {
+        let rest = &text[i..];
+        let until = |open : usize, close : &str| rest[open..].find(close).map(|j| open + j + close.len()).unwrap_or(rest.len());
+
+        let len = if rest.starts_with("r#\"") {
+            until(3, "\"#")
+        } else if rest.starts_with('"') {
+            until(1, "\"")
+        } else if rest.starts_with("/*") {
+            until(2, "*/")
+        } else if let Some(comment) = rest.strip_prefix("//") {
+            let end = comment.find('\n').unwrap_or(comment.len());
+            found.push((line, &text[line_start..i], &comment[..end]));
+            end + 2
+        } else {
+            rest.chars().next().map_or(1, |c| c.len_utf8())
+        };
+
+        for (j, c) in rest[..len].char_indices() {
+            if c == '\n' {
+                line += 1;
+                line_start = i + j + 1;
+            }
+        }
+        i += len;
+    }
+
+    found
+}
+
+/// the mistakes that would only show once the program runs, or never. \
+/// functions and globals `env` already knows count as defined, so do the ones `tree` defines anywhere
+pub fn check(tree : &Vast, env : &Environment, lints : &Lints) -> Vec<Problem> {
+    let mut c = Checker { env, lints, functions : HashMap::new(), globals : HashSet::new(), problems : Vec::new() };
+
+    for a in tree {
+        walk(a, &mut |a| match a {
+            Ast::FunDef { name, args, .. } | Ast::StructDef { name, fields : args, .. } => {
+                c.functions.insert(name, args);
+            },
+            Ast::Import { path, alias } => {
+                c.globals.insert(alias.clone().unwrap_or_else(|| module_name(Path::new(path))));
+            },
+            _ => {},
+        });
+        c.globals.extend(binds(a).into_iter().map(|(n, _)| n.clone()));
+    }
+
+    let top = Scope { params : HashSet::new(), locals : HashSet::new() };
+    for a in tree {
+        c.visit(a, &top);
+    }
+
+    c.problems.sort_by_key(|p| p.span.unwrap_or_default());
+    c.problems
+}
+
+/// calls f on a and everything inside it, parents first
+pub fn walk<'a>(a : &'a Ast, f : &mut impl FnMut(&'a Ast) -> ()) -> () {
+    f(a);
+    for c in a.children() {
+        walk(c, f);
+    }
+}
+
+/// the variables a function can see besides the globals
+struct Scope<'a> {
+    params : HashSet<&'a str>,
+    locals : HashSet<&'a str>,
+}
+
+impl Scope<'_> {
+    fn has(&self, name : &str) -> bool {
+        self.params.contains(name) || self.locals.contains(name)
+    }
+}
+
+struct Checker<'a> {
+    env : &'a Environment,
+    lints : &'a Lints,
+    /// script functions and structs by name, with their parameters or fields
+    functions : HashMap<&'a str, &'a Vec<Param>>,
+    /// variables assigned outside of functions and import aliases
+    globals : HashSet<String>,
+    problems : Vec<Problem>,
+}
+
+impl<'a> Checker<'a> {
+    fn report(&mut self, lint : &'static str, span : Option<Span>, message : String) -> () {
+        let severity = self.lints.severity(lint);
+        if severity != Severity::Allow {
+            self.problems.push(Problem { lint, severity, span, message });
+        }
+    }
+
+    /// a global variable, function or builtin
+    fn known(&self, name : &str) -> bool {
+        let name = String::from(name);
+        self.globals.contains(&name) || self.functions.contains_key(name.as_str()) || self.env.is_callable(&name) || self.env.fetch(&name).is_some()
+    }
+
+    fn visit(&mut self, a : &'a Ast, scope : &Scope<'a>) -> () {
+        match a {
+            Ast::FunDef { name, args, body, span } => {
+                self.redefined(name, *span);
+                self.parameters(name, args, true);
+
+                let locals : Vec<(&String, Option<Span>)> = binds(body);
+                let inner = Scope { params : args.iter().map(|p| p.name.as_str()).collect(), locals : locals.iter().map(|(n, _)| n.as_str()).collect() };
+
+                for p in args {
+                    if let Some(d) = &p.default {
+                        self.visit(d, &inner);
+                    }
+                    if self.functions.contains_key(p.name.as_str()) || self.env.is_callable(&p.name) {
+                        self.report("shadowed-parameter", Some(p.span), format!("the parameter {} of {} hides the function {}", p.name, name, p.name));
+                    }
+                }
+                self.visit(body, &inner);
+
+                for (local, at) in locals.iter() {
+                    if inner.params.contains(local.as_str()) {
+                        self.report("shadowed-parameter", at.or(Some(*span)), format!("{} overwrites the parameter {} of {}", local, local, name));
+                    }
+                }
+                self.unused(name, body, &locals, &inner);
+                return;
+            },
+            Ast::StructDef { name, fields, span } => {
+                self.redefined(name, *span);
+                self.parameters(name, fields, false);
+
+                // defaults see the fields before them
+                let inner = Scope { params : fields.iter().map(|p| p.name.as_str()).collect(), locals : HashSet::new() };
+                for d in fields.iter().filter_map(|p| p.default.as_ref()) {
+                    self.visit(d, &inner);
+                }
+                return;
+            },
+            Ast::Get(name, span) if !scope.has(name) && !self.known(name) => {
+                self.report("unbound-variable", Some(*span), format!("{} is never assigned", name));
+            },
+            // `alias.f` depends on what the imported file defines
+            Ast::Call { name, with, kwargs, span } if !name.contains('.') => {
+                if !scope.has(name) && !self.known(name) {
+                    self.report("undefined-function", Some(*span), format!("call to undefined function {}", name));
+                } else if !scope.has(name) && !self.globals.contains(name) {
+                    self.arity(name, with.len(), kwargs, *span);
+                }
+            },
+            Ast::If { if_, else_, span, .. } => {
+                self.condition(if_);
+
+                if let Ast::Literal(v) = &**if_ {
+                    match v.clone().as_bool() {
+                        Value::Bool(false) => self.report("unreachable", Some(*span), format!("the condition is always false, this branch is never taken")),
+                        Value::Bool(true) if else_.as_deref().map(|e| !matches!(e, Ast::Literal(Value::Nil))).unwrap_or(false) => {
+                            self.report("unreachable", Some(*span), format!("the condition is always true, the branches after it are never taken"));
+                        },
+                        _ => {},
+                    }
+                }
+            },
+            Ast::While { cond, .. } => self.condition(cond),
+            Ast::Match { arms, .. } => {
+                let catch_all = arms.iter().position(|arm| arm.guard.is_none() && arm.pattern.names().len() <= 1 && matches!(arm.pattern, Pattern::Wildcard | Pattern::Bind(_)));
+                if let Some(i) = catch_all {
+                    if let Some(arm) = arms.get(i + 1) {
+                        self.report("unreachable", Some(arm.span), format!("the arm {} matches everything, the arms after it are never taken", arms[i].pattern));
+                    }
+                }
+            },
+            _ => {},
+        }
+
+        for c in a.children() {
+            self.visit(c, scope);
+        }
+    }
+
+    /// the name check push_function and push_struct make
+    fn redefined(&mut self, name : &str, span : Span) -> () {
+        if is_builtin(name) {
+            self.report("redefined-builtin", Some(span), format!("{} is a builtin and can not be redefined", name));
+        }
+    }
+
+    /// the parameter checks push_function and push_struct make
+    fn parameters(&mut self, name : &str, params : &[Param], rest : bool) -> () {
+        for (i, p) in params.iter().enumerate() {
+            if params[..i].iter().any(|q| q.name == p.name) {
+                self.report("invalid-parameters", Some(p.span), format!("in {} : duplicate parameter {}", name, p.name));
+            }
+            if p.rest && !rest {
+                self.report("invalid-parameters", Some(p.span), format!("in {} : a field can not be a rest parameter (...{})", name, p.name));
+            }
+            if p.rest && rest && i + 1 != params.len() {
+                self.report("invalid-parameters", Some(p.span), format!("in {} : the rest parameter ...{} must be the last one", name, p.name));
+            }
+        }
+    }
+
+    /// a call with `positional` arguments and `kwargs` to a builtin, function or struct
+    fn arity(&mut self, name : &String, positional : usize, kwargs : &Vec<(String, Ast)>, span : Span) -> () {
+        if let Some((min, max)) = builtins::arity_of(name) {
+            if !kwargs.is_empty() {
+                self.report("arity", Some(span), format!("{} does not take keyword arguments", name));
+            } else if positional < min || positional > max {
+                let expected = match (min, max) {
+                    (min, max) if min == max => format!("{}", min),
+                    (min, usize::MAX) => format!("at least {}", min),
+                    (min, max) => format!("{} to {}", min, max),
+                };
+                self.report("arity", Some(span), format!("{} takes {} arguments but {} were given", name, expected, positional));
+            }
+            return;
+        }
+
+        let Some(params) = self.functions.get(name.as_str()).copied() else {
+            return;
+        };
+
+        let rest = params.iter().any(|p| p.rest);
+        let named : Vec<&Param> = params.iter().filter(|p| !p.rest).collect();
+
+        if positional > named.len() && !rest {
+            self.report("arity", Some(span), format!("{} takes at most {} arguments but {} were given", name, named.len(), positional));
+        }
+        for (k, _) in kwargs {
+            if !named.iter().any(|p| p.name == *k) {
+                self.report("arity", Some(span), format!("{} has no parameter {}", name, k));
+            }
+        }
+        for p in named.iter().skip(positional) {
+            if p.default.is_none() && !kwargs.iter().any(|(k, _)| *k == p.name) {
+                self.report("arity", Some(span), format!("{} is missing the argument {}", name, p.name));
+            }
+        }
+    }
+
+    fn condition(&mut self, cond : &Ast) -> () {
+        if let Ast::Set { name, span, .. } = cond {
+            self.report("assignment-in-condition", Some(*span), format!("the condition assigns {}, use == to compare", name));
+        }
+    }
+
+    /// variables `function` assigns and never reads. they might be globals when a global of that name exists
+    fn unused(&mut self, function : &String, body : &'a Ast, locals : &Vec<(&'a String, Option<Span>)>, scope : &Scope<'a>) -> () {
+        let mut read = HashSet::new();
+        walk_function(body, &mut |a| match a {
+            Ast::Get(name, _) | Ast::Call { name, .. } => {
+                read.insert(name.as_str());
+            },
+            _ => {},
+        });
+
+        let mut reported = HashSet::new();
+        for (name, span) in locals {
+            let Some(span) = span else {
+                continue;
+            };
+            if name.starts_with('_') || read.contains(name.as_str()) || scope.params.contains(name.as_str()) || self.globals.contains(*name) || !reported.insert(name) {
+                continue;
+            }
+            self.report("unused-variable", Some(*span), format!("{} is assigned in {} but never read", name, function));
+        }
+    }
+}
+
+/// like walk but does not enter the functions defined inside `a`
+pub fn walk_function<'a>(a : &'a Ast, f : &mut impl FnMut(&'a Ast) -> ()) -> () {
+    f(a);
+    if let Ast::FunDef { .. } = a {
+        return;
+    }
+    for c in a.children() {
+        walk_function(c, f);
+    }
+}
+
+/// the variables `a` assigns outside of the functions it defines, with the span of assignments
+fn binds(a : &Ast) -> Vec<(&String, Option<Span>)> {
+    let mut found = Vec::new();
+
+    walk_function(a, &mut |a| match a {
+        Ast::Set { name, span, .. } => found.push((name, Some(*span))),
+        Ast::Try { name, .. } => found.push((name, None)),
+        Ast::Destructure { pattern, .. } => found.extend(pattern.names().into_iter().map(|n| (n, None))),
+        Ast::Match { arms, .. } => {
+            for arm in arms {
+                found.extend(arm.pattern.names().into_iter().map(|n| (n, Some(arm.span))));
+            }
+        },
+        _ => {},
+    });
+
+    found
+}
+
+#[test]
+fn test_check() -> () {
+    let env = Environment::new();
+    let text = "fn f(a, a) { a };
+struct P { x, ...y };
+g = f;
+g(1);
+h(2);
+m.k(3);
+fn area(w, h = 1) { [unused = 2; w * h * depth;] };
+area(1, 2, 3);
+area(h = 2);
+len(1, 2);
+{ 1 : 2, 3 };
+while x = 0 : [];
+fn swap(l) { [[l, b] = l; len = 1; match l { _ => 1, [] => 2 };] };
+fn quiet(len) { y = 1 }; // lint: allow(unused-variable, shadowed-parameter)
+fn upper(s) { s };
+displn(\"// lint: allow(all)\"); h(3);
+k(\"a//b\"); // lint: allow(undefined-function)
+";
+
+    let problems : Vec<String> = lint(text, &env, &Lints::new()).unwrap().iter().map(|p| format!("{}", p)).collect();
+    assert_eq!(problems, vec![
+        "1:9 : error : in f : duplicate parameter a [invalid-parameters]",
+        "2:18 : error : in P : a field can not be a rest parameter (...y) [invalid-parameters]",
+        "5:1 : error : call to undefined function h [undefined-function]",
+        "7:22 : warning : unused is assigned in area but never read [unused-variable]",
+        "7:42 : error : depth is never assigned [unbound-variable]",
+        "8:1 : error : area takes at most 2 arguments but 3 were given [arity]",
+        "9:1 : error : area is missing the argument w [arity]",
+        "10:1 : error : len takes 1 arguments but 2 were given [arity]",
+        "11:5 : warning : the condition is always true, the branches after it are never taken [unreachable]",
+        "12:7 : warning : the condition assigns x, use == to compare [assignment-in-condition]",
+        "13:4 : warning : l overwrites the parameter l of swap [shadowed-parameter]",
+        "13:27 : warning : len is assigned in swap but never read [unused-variable]",
+        "13:57 : warning : the arm _ matches everything, the arms after it are never taken [unreachable]",
+        "15:4 : error : upper is a builtin and can not be redefined [redefined-builtin]",
+        "16:32 : error : call to undefined function h [undefined-function]",
+    ]);
+
+    let mut lints = Lints::new();
+    lints.set("unreachable", Severity::Allow).unwrap();
+    lints.set("arity", Severity::Warning).unwrap();
+    assert!(lints.set("typo", Severity::Allow).is_err());
+
+    let problems = lint("len(1, 2); { 0 : 1 };", &env, &lints).unwrap();
+    assert_eq!(problems.iter().map(|p| (p.lint, p.severity)).collect::<Vec<_>>(), vec![("arity", Severity::Warning)]);
+}
@@ -0,0 +1,339 @@
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::io::{BufRead, Read, Write};
+use std::path::Path;
+use std::rc::Rc;
+
+use serde_json::{json, Value as Json};
+
+use super::debug::{Command, Debugger, Frontend, Paused};
+use super::error::{summarize, ErrorKind};
+use super::run::Runtime;
+
+/// the only thread of a program
+const THREAD : i64 = 1;
+
+/// messages of the Debug Adapter Protocol, JSON with a Content-Length header like the language server protocol
+struct Transport {
+    input : Box<dyn BufRead>,
+    output : Box<dyn Write>,
+    seq : i64,
+}
+
+impl Transport {
+    /// the next message, None at the end of the input or when it is not a message
+    fn read(&mut self) -> Option<Json> {
+        let mut length = None;
+        loop {
+            let mut line = String::new();
+            if self.input.read_line(&mut line).ok()? == 0 {
+                return None;
+            }
+
+            let line = line.trim();
+            if line.is_empty() {
+                break;
+            }
+            if let Some(l) = line.strip_prefix("Content-Length:") {
+                length = l.trim().parse::<usize>().ok();
+            }
+        }
+
+        let mut body = vec![0; length?];
+        self.input.read_exact(&mut body).ok()?;
+        serde_json::from_slice(&body).ok()
+    }
+
+    fn send(&mut self, mut message : Json) -> () {
+        self.seq += 1;
+        message["seq"] = json!(self.seq);
+
+        let body = message.to_string();
+        // the client is gone when this fails, the program runs to its end regardless
+        let _ = write!(self.output, "Content-Length: {}\r\n\r\n{}", body.len(), body);
+        let _ = self.output.flush();
+    }
+
+    fn respond(&mut self, req : &Json, body : Json) -> () {
+        self.send(json!({ "type" : "response", "request_seq" : req["seq"], "command" : req["command"], "success" : true, "body" : body }));
+    }
+
+    fn fail(&mut self, req : &Json, message : String) -> () {
+        self.send(json!({ "type" : "response", "request_seq" : req["seq"], "command" : req["command"], "success" : false, "message" : message }));
+    }
+
+    fn event(&mut self, event : &str, body : Json) -> () {
+        self.send(json!({ "type" : "event", "event" : event, "body" : body }));
+    }
+}
+
+/// what the program writes, sent to the client as output events
+struct Output {
+    transport : Rc<RefCell<Transport>>,
+    category : &'static str,
+}
+
+impl Write for Output {
+    fn write(&mut self, data : &[u8]) -> std::io::Result<usize> {
+        let output = String::from_utf8_lossy(data);
+        self.transport.borrow_mut().event("output", json!({ "category" : self.category, "output" : output }));
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// `debug --dap` of the binary, serves one debugging session on `input` and `output`
+pub fn serve(input : Box<dyn BufRead>, output : Box<dyn Write>) -> () {
+    let transport = Rc::new(RefCell::new(Transport { input, output, seq : 0 }));
+    let mut breakpoints = BTreeSet::new();
+    let mut launch = None;
+
+    // configuration, the program starts once the client says it is done
+    loop {
+        let Some(req) = transport.borrow_mut().read() else {
+            return;
+        };
+        let mut t = transport.borrow_mut();
+
+        match req["command"].as_str().unwrap_or("") {
+            "initialize" => {
+                t.respond(&req, json!({ "supportsConfigurationDoneRequest" : true, "supportsEvaluateForHovers" : true }));
+                t.event("initialized", json!({}));
+            },
+            "launch" => {
+                let path = req["arguments"]["program"].as_str().unwrap_or("");
+                match std::fs::read_to_string(path) {
+                    Ok(text) => {
+                        t.respond(&req, json!({}));
+                        launch = Some((String::from(path), text, req["arguments"].clone()));
+                    },
+                    Err(e) => t.fail(&req, format!("could not read {} : {}", path, e)),
+                }
+            },
+            "setBreakpoints" => breakpoints = set_breakpoints(&mut t, &req),
+            "configurationDone" => {
+                t.respond(&req, json!({}));
+                if launch.is_some() {
+                    break;
+                }
+            },
+            "disconnect" | "terminate" => {
+                t.respond(&req, json!({}));
+                return;
+            },
+            _ => common(&mut t, &req),
+        }
+    }
+
+    let Some((path, text, arguments)) = launch else {
+        return;
+    };
+
+    let mut r = Runtime::new();
+    r.set_output(Box::new(Output { transport : transport.clone(), category : "stdout" }));
+    r.set_diagnostics(Box::new(Output { transport : transport.clone(), category : "stderr" }));
+    r.set_args(arguments["args"].as_array().into_iter().flatten().filter_map(|a| a.as_str()).map(String::from).collect());
+
+    let stop_on_entry = arguments["stopOnEntry"].as_bool().unwrap_or(false);
+    let adapter = Adapter { transport : transport.clone(), path : path.clone() };
+    r.set_debugger(Debugger::new(Box::new(adapter), breakpoints, stop_on_entry));
+
+    let code = match r.parse(&text) {
+        Ok(tree) => {
+            let file = Path::new(&path).canonicalize().unwrap_or_else(|_| Path::new(&path).to_path_buf());
+            r.environment().loading.push(file);
+            let ret = r.execute(&tree);
+            r.environment().loading.pop();
+
+            match ret {
+                Ok(_) => 0,
+                Err(e) if e.kind == ErrorKind::Exit => e.exit_code(),
+                Err(e) => {
+                    transport.borrow_mut().event("output", json!({ "category" : "stderr", "output" : format!("{}\n", e) }));
+                    1
+                },
+            }
+        },
+        Err(e) => {
+            transport.borrow_mut().event("output", json!({ "category" : "stderr", "output" : format!("{} : {}\n", path, e) }));
+            1
+        },
+    };
+
+    let mut t = transport.borrow_mut();
+    t.event("exited", json!({ "exitCode" : code }));
+    t.event("terminated", json!({}));
+
+    while let Some(req) = t.read() {
+        match req["command"].as_str().unwrap_or("") {
+            "disconnect" | "terminate" => {
+                t.respond(&req, json!({}));
+                return;
+            },
+            _ => t.fail(&req, format!("the program has ended")),
+        }
+    }
+}
+
+/// a client can only set breakpoints on lines of the program it launched
+fn set_breakpoints(t : &mut Transport, req : &Json) -> BTreeSet<usize> {
+    let lines : BTreeSet<usize> = req["arguments"]["breakpoints"].as_array().into_iter().flatten()
+        .filter_map(|b| b["line"].as_u64())
+        .map(|l| l as usize)
+        .collect();
+
+    let verified : Vec<Json> = lines.iter().map(|l| json!({ "verified" : true, "line" : l })).collect();
+    t.respond(req, json!({ "breakpoints" : verified }));
+    lines
+}
+
+/// requests answered the same whether the program is paused or not
+fn common(t : &mut Transport, req : &Json) -> () {
+    match req["command"].as_str().unwrap_or("") {
+        "threads" => t.respond(req, json!({ "threads" : [{ "id" : THREAD, "name" : "main" }] })),
+        "setExceptionBreakpoints" => t.respond(req, json!({})),
+        c => t.fail(req, format!("{} is not supported", c)),
+    }
+}
+
+/// answers requests about the paused program until one resumes it
+struct Adapter {
+    transport : Rc<RefCell<Transport>>,
+    path : String,
+}
+
+impl Frontend for Adapter {
+    fn paused(&mut self, p : &mut Paused<'_>) -> Command {
+        self.transport.borrow_mut().event("stopped", json!({ "reason" : p.reason.name(), "threadId" : THREAD, "allThreadsStopped" : true }));
+
+        loop {
+            let Some(req) = self.transport.borrow_mut().read() else {
+                return Command::Quit;
+            };
+
+            let resume = match req["command"].as_str().unwrap_or("") {
+                "continue" => Some(Command::Continue),
+                "next" => Some(Command::StepOver),
+                "stepIn" => Some(Command::StepInto),
+                "stepOut" => Some(Command::StepOut),
+                "disconnect" | "terminate" => Some(Command::Quit),
+                _ => None,
+            };
+            if let Some(c) = resume {
+                self.transport.borrow_mut().respond(&req, json!({ "allThreadsContinued" : true }));
+                return c;
+            }
+
+            self.request(p, &req);
+        }
+    }
+}
+
+impl Adapter {
+    fn request(&mut self, p : &mut Paused<'_>, req : &Json) -> () {
+        let args = &req["arguments"];
+
+        match req["command"].as_str().unwrap_or("") {
+            // frames are numbered from the innermost, scopes by their index in envs plus one since 0 means none
+            "stackTrace" => {
+                let frames : Vec<Json> = p.stack().iter().enumerate().map(|(i, e)| json!({
+                    "id" : i,
+                    "name" : e.name,
+                    "line" : e.at.line,
+                    "column" : e.at.column,
+                    "source" : { "path" : self.path },
+                })).collect();
+                let total = frames.len();
+                self.transport.borrow_mut().respond(req, json!({ "stackFrames" : frames, "totalFrames" : total }));
+            },
+            "scopes" => {
+                let stack = p.stack();
+                let frame = stack.get(args["frameId"].as_u64().unwrap_or(0) as usize);
+
+                let mut scopes = Vec::new();
+                if let Some(s) = frame.and_then(|f| f.scope).filter(|s| *s > 0) {
+                    scopes.push(json!({ "name" : "Locals", "variablesReference" : s + 1, "expensive" : false }));
+                }
+                scopes.push(json!({ "name" : "Globals", "variablesReference" : 1, "expensive" : false }));
+                self.transport.borrow_mut().respond(req, json!({ "scopes" : scopes }));
+            },
+            "variables" => {
+                let scope = (args["variablesReference"].as_u64().unwrap_or(0) as usize).checked_sub(1);
+                let variables : Vec<Json> = scope.map(|s| p.variables(s)).unwrap_or_default().iter().map(|(name, v)| json!({
+                    "name" : name,
+                    "value" : summarize(v),
+                    "type" : v.type_name(),
+                    "variablesReference" : 0,
+                })).collect();
+                self.transport.borrow_mut().respond(req, json!({ "variables" : variables }));
+            },
+            // expressions run in the innermost call whatever frame the client picked
+            "evaluate" => match p.evaluate(args["expression"].as_str().unwrap_or("")) {
+                Ok(v) => self.transport.borrow_mut().respond(req, json!({ "result" : format!("{}", v), "variablesReference" : 0 })),
+                Err(e) => self.transport.borrow_mut().fail(req, e),
+            },
+            "setBreakpoints" => *p.breakpoints = set_breakpoints(&mut self.transport.borrow_mut(), req),
+            _ => common(&mut self.transport.borrow_mut(), req),
+        }
+    }
+}
+
+#[test]
+fn test_dap() -> () {
+    use super::output::SharedBuffer;
+
+    let dir = std::env::temp_dir().join(format!("dap_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let program = dir.join("main.txt");
+    std::fs::write(&program, "fn sq(x) [\n    y = x * x;\n    y;\n];\na = sq(3);\ndispln(a);\n").unwrap();
+
+    let requests = [
+        json!({ "seq" : 1, "type" : "request", "command" : "initialize", "arguments" : { "adapterID" : "script" } }),
+        json!({ "seq" : 2, "type" : "request", "command" : "launch", "arguments" : { "program" : program } }),
+        json!({ "seq" : 3, "type" : "request", "command" : "setBreakpoints", "arguments" : { "source" : { "path" : program }, "breakpoints" : [{ "line" : 2 }] } }),
+        json!({ "seq" : 4, "type" : "request", "command" : "configurationDone" }),
+        json!({ "seq" : 5, "type" : "request", "command" : "stackTrace", "arguments" : { "threadId" : 1 } }),
+        json!({ "seq" : 6, "type" : "request", "command" : "scopes", "arguments" : { "frameId" : 0 } }),
+        json!({ "seq" : 7, "type" : "request", "command" : "variables", "arguments" : { "variablesReference" : 2 } }),
+        json!({ "seq" : 8, "type" : "request", "command" : "evaluate", "arguments" : { "expression" : "x * 2", "context" : "watch" } }),
+        json!({ "seq" : 9, "type" : "request", "command" : "next", "arguments" : { "threadId" : 1 } }),
+        json!({ "seq" : 10, "type" : "request", "command" : "evaluate", "arguments" : { "expression" : "y", "context" : "hover" } }),
+        json!({ "seq" : 11, "type" : "request", "command" : "continue", "arguments" : { "threadId" : 1 } }),
+        json!({ "seq" : 12, "type" : "request", "command" : "disconnect" }),
+    ];
+    let input : String = requests.iter().map(|r| format!("Content-Length: {}\r\n\r\n{}", r.to_string().len(), r)).collect();
+
+    let output = SharedBuffer::new();
+    serve(Box::new(std::io::Cursor::new(input)), Box::new(output.clone()));
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let mut transport = Transport { input : Box::new(std::io::Cursor::new(output.contents())), output : Box::new(std::io::sink()), seq : 0 };
+    let mut messages = Vec::new();
+    while let Some(m) = transport.read() {
+        messages.push(m);
+    }
+
+    let summary : Vec<String> = messages.iter().map(|m| match m["type"].as_str().unwrap() {
+        "event" => format!("event {}", m["event"].as_str().unwrap()),
+        _ => format!("{} {}", m["command"].as_str().unwrap(), m["success"]),
+    }).collect();
+    assert_eq!(summary, vec![
+        "initialize true", "event initialized", "launch true", "setBreakpoints true", "configurationDone true",
+        "event stopped", "stackTrace true", "scopes true", "variables true", "evaluate true", "next true",
+        "event stopped", "evaluate true", "continue true", "event output", "event exited", "event terminated", "disconnect true",
+    ]);
+
+    let body = |i : usize| &messages[i]["body"];
+    assert_eq!(body(5)["reason"], json!("breakpoint"));
+    assert_eq!(body(6)["stackFrames"].as_array().unwrap().iter().map(|f| (f["name"].as_str().unwrap(), f["line"].as_u64().unwrap())).collect::<Vec<_>>(), vec![("sq", 2), ("main", 5)]);
+    assert_eq!(body(7)["scopes"].as_array().unwrap().iter().map(|s| s["name"].as_str().unwrap()).collect::<Vec<_>>(), vec!["Locals", "Globals"]);
+    assert_eq!(body(8)["variables"], json!([{ "name" : "x", "value" : "3", "type" : "int", "variablesReference" : 0 }]));
+    assert_eq!(body(9)["result"], json!("6"));
+    assert_eq!(body(11)["reason"], json!("step"));
+    assert_eq!(body(12)["result"], json!("9"));
+    assert_eq!(body(14)["output"], json!("9\n"));
+    assert_eq!(body(15)["exitCode"], json!(0));
+}
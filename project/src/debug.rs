@@ -0,0 +1,438 @@
+use std::collections::BTreeSet;
+use std::io::{BufRead, Write};
+use std::time::Instant;
+
+use super::ast::Span;
+use super::builtins;
+use super::environment::{Environment, BUILTINS};
+use super::error::{summarize, RuntimeError};
+use super::value::Value;
+
+/// how the program goes on after a pause
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Command {
+    /// until a breakpoint
+    Continue,
+    /// until the next line, entering calls
+    StepInto,
+    /// until the next line of this function or of a caller
+    StepOver,
+    /// until the function returns to its caller
+    StepOut,
+    /// stops the program as `exit(1)` would
+    Quit,
+}
+
+/// why the program paused, named like in the Debug Adapter Protocol
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Reason {
+    Entry,
+    Breakpoint,
+    Step,
+}
+
+impl Reason {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Reason::Entry => "entry",
+            Reason::Breakpoint => "breakpoint",
+            Reason::Step => "step",
+        }
+    }
+}
+
+/// what the user sees and does while the program is paused
+pub trait Frontend {
+    fn paused(&mut self, p : &mut Paused<'_>) -> Command;
+}
+
+/// pauses a program on lines of the file being debugged, code of imported modules runs without stopping. \
+/// a line pauses once each time it is reached from another line, so a loop on a single line pauses once
+pub struct Debugger {
+    pub breakpoints : BTreeSet<usize>,
+    /// expressions evaluated at every pause
+    pub watches : Vec<String>,
+    frontend : Box<dyn Frontend>,
+    command : Command,
+    /// the call depth when the last command was given
+    from : usize,
+    /// the line last reached at each call depth
+    lines : Vec<usize>,
+    entry : bool,
+}
+
+impl Debugger {
+    /// pauses on the first line when stop_on_entry, otherwise at the first breakpoint
+    pub fn new(frontend : Box<dyn Frontend>, breakpoints : BTreeSet<usize>, stop_on_entry : bool) -> Debugger {
+        let command = if stop_on_entry { Command::StepInto } else { Command::Continue };
+        Debugger { breakpoints, watches : Vec::new(), frontend, command, from : 0, lines : Vec::new(), entry : stop_on_entry }
+    }
+
+    fn hook(&mut self, env : &mut Environment, at : Span) -> Result<(), RuntimeError> {
+        if env.namespace.is_some() {
+            return Ok(());
+        }
+
+        // deeper lines belong to calls that returned, the next call starts afresh
+        let depth = env.depth;
+        let returned = self.command == Command::StepOut && depth < self.from;
+        self.lines.truncate(depth + 1);
+        if self.lines.get(depth) == Some(&at.line) && !returned {
+            return Ok(());
+        }
+        self.lines.resize(depth + 1, 0);
+        self.lines[depth] = at.line;
+
+        let stepped = match self.command {
+            Command::StepInto => true,
+            Command::StepOver => depth <= self.from,
+            Command::StepOut => returned,
+            Command::Continue | Command::Quit => false,
+        };
+        let reason = match (stepped, self.entry) {
+            (true, true) => Reason::Entry,
+            (true, false) => Reason::Step,
+            (false, _) if self.breakpoints.contains(&at.line) => Reason::Breakpoint,
+            (false, _) => return Ok(()),
+        };
+        self.entry = false;
+
+        let _ = env.output.flush();
+        let paused_at = Instant::now();
+
+        let mut p = Paused { env, at, reason, breakpoints : &mut self.breakpoints, watches : &mut self.watches };
+        self.command = self.frontend.paused(&mut p);
+        self.from = depth;
+
+        // the time spent paused does not count against the time limit
+        if let Some(deadline) = env.deadline.as_mut() {
+            *deadline += paused_at.elapsed();
+        }
+
+        match self.command {
+            Command::Quit => Err(RuntimeError::exit(1)),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Environment {
+    /// gives the debugger a chance to pause before the node recorded at `at`
+    pub fn debug(&mut self, at : Span) -> Result<(), RuntimeError> {
+        // taken out while it runs so expressions evaluated in a pause do not pause
+        let Some(mut debugger) = self.debugger.take() else {
+            return Ok(());
+        };
+
+        let ret = debugger.hook(self, at);
+        self.debugger = Some(debugger);
+        ret
+    }
+}
+
+/// a function call of the paused program
+#[derive(Debug, Clone)]
+pub struct StackEntry {
+    pub name : String,
+    /// where it is at, the call it is making or the paused line for the innermost one
+    pub at : Span,
+    /// the index of its variables in Environment::envs, None for builtins
+    pub scope : Option<usize>,
+}
+
+/// the program while it is paused, what frontends inspect it with
+pub struct Paused<'a> {
+    pub env : &'a mut Environment,
+    pub at : Span,
+    pub reason : Reason,
+    pub breakpoints : &'a mut BTreeSet<usize>,
+    pub watches : &'a mut Vec<String>,
+}
+
+impl Paused<'_> {
+    /// innermost call first, the program itself is the last entry
+    pub fn stack(&self) -> Vec<StackEntry> {
+        let mut stack = Vec::new();
+        let mut at = self.at;
+        let mut scope = self.env.wenv();
+
+        for f in self.env.frames.iter().rev() {
+            let builtin = BUILTINS.contains(&f.name.as_str()) || builtins::get(&f.name).is_some() || self.env.natives.contains_key(&f.name);
+            stack.push(StackEntry { name : f.name.clone(), at, scope : if builtin { None } else { Some(scope) } });
+
+            if !builtin {
+                scope = scope.saturating_sub(1);
+            }
+            at = f.call_site;
+        }
+
+        stack.push(StackEntry { name : String::from("main"), at, scope : Some(0) });
+        stack
+    }
+
+    /// the variables of envs[scope] sorted by name, the globals of the program for 0
+    pub fn variables(&self, scope : usize) -> Vec<(String, Value)> {
+        let Some(env) = self.env.envs.get(scope) else {
+            return vec![];
+        };
+
+        let mut v : Vec<(String, Value)> = env.iter()
+            .filter(|(k, _)| scope > 0 || !k.contains('.'))
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        v.sort_by(|a, b| a.0.cmp(&b.0));
+        v
+    }
+
+    /// runs `text` in the paused frame, assignments change the program's variables
+    pub fn evaluate(&mut self, text : &str) -> Result<Value, String> {
+        let mut text = String::from(text.trim());
+        if !text.ends_with(';') {
+            text.push(';');
+        }
+
+        let tree = self.env.syntax.parse(&text)?;
+        let mut v = Value::Nil;
+        for a in tree.iter() {
+            v = a.eval(self.env).map_err(|e| format!("{}", e))?;
+        }
+
+        Ok(v)
+    }
+
+    /// every watch expression with its value or error
+    pub fn watched(&mut self) -> Vec<(String, Result<Value, String>)> {
+        let watches = self.watches.clone();
+        watches.into_iter().map(|w| {
+            let v = self.evaluate(&w);
+            (w, v)
+        }).collect()
+    }
+}
+
+const HELP : &str = "\
+    c, continue      run until a breakpoint
+    s, step          run to the next line, entering calls
+    n, next          run to the next line of this function
+    o, out           run until this function returns
+    b LINE           pause on LINE
+    d LINE           remove the breakpoint on LINE
+    breaks           list the breakpoints
+    bt               the calls of the paused program, innermost first
+    vars [N]         the variables of call N of bt, the innermost by default
+    p EXPR           evaluate EXPR in the paused call
+    w EXPR           evaluate EXPR at every pause
+    unwatch N        stop evaluating watch N
+    l                the source around the paused line
+    q, quit          stop the program
+An empty line repeats the last command, the end of the input runs the rest of the program.";
+
+/// a line based frontend, `debug FILE` of the binary
+pub struct Console {
+    source : Vec<String>,
+    input : Box<dyn BufRead>,
+    output : Box<dyn Write>,
+    /// the command an empty line repeats
+    last : String,
+}
+
+impl Console {
+    /// `source` is the text of the program, for listing it
+    pub fn new(source : &str, input : Box<dyn BufRead>, output : Box<dyn Write>) -> Console {
+        Console { source : source.lines().map(String::from).collect(), input, output, last : String::new() }
+    }
+
+    fn list(&mut self, line : usize, around : usize) -> () {
+        let from = line.saturating_sub(around).max(1);
+        for n in from..=(line + around).min(self.source.len()) {
+            let marker = if n == line { ">" } else { " " };
+            let _ = writeln!(self.output, "{} {:>4} | {}", marker, n, self.source[n - 1]);
+        }
+    }
+
+    /// Some when the command resumes the program
+    fn command(&mut self, p : &mut Paused<'_>, line : &str) -> Option<Command> {
+        let (command, arg) = line.split_once(' ').map(|(c, a)| (c, a.trim())).unwrap_or((line, ""));
+        let out = &mut self.output;
+
+        match command {
+            "c" | "continue" => return Some(Command::Continue),
+            "s" | "step" => return Some(Command::StepInto),
+            "n" | "next" => return Some(Command::StepOver),
+            "o" | "out" => return Some(Command::StepOut),
+            "q" | "quit" => return Some(Command::Quit),
+            "b" | "d" => match arg.parse::<usize>() {
+                Ok(n) if command == "b" => {
+                    p.breakpoints.insert(n);
+                },
+                Ok(n) => {
+                    if !p.breakpoints.remove(&n) {
+                        let _ = writeln!(out, "there is no breakpoint on line {}", n);
+                    }
+                },
+                Err(_) => {
+                    let _ = writeln!(out, "{} takes a line number", command);
+                },
+            },
+            "breaks" => {
+                let lines : Vec<String> = p.breakpoints.iter().map(|l| format!("{}", l)).collect();
+                let _ = writeln!(out, "breakpoints : {}", lines.join(", "));
+            },
+            "bt" => {
+                for (i, e) in p.stack().iter().enumerate() {
+                    let _ = writeln!(out, "#{} {} at {}", i, e.name, e.at);
+                }
+            },
+            "vars" => {
+                let stack = p.stack();
+                let n = if arg.is_empty() { Ok(0) } else { arg.parse::<usize>() };
+
+                match n.ok().and_then(|n| stack.get(n)) {
+                    Some(StackEntry { scope : Some(s), .. }) => {
+                        for (name, v) in p.variables(*s) {
+                            let _ = writeln!(out, "{} = {}", name, summarize(&v));
+                        }
+                    },
+                    Some(e) => {
+                        let _ = writeln!(out, "{} is a builtin", e.name);
+                    },
+                    None => {
+                        let _ = writeln!(out, "there is no call {}, see bt", arg);
+                    },
+                }
+            },
+            "p" => match p.evaluate(arg) {
+                Ok(v) => {
+                    let _ = writeln!(out, "{}", v);
+                },
+                Err(e) => {
+                    let _ = writeln!(out, "{}", e);
+                },
+            },
+            "w" => {
+                p.watches.push(String::from(arg));
+                let v = p.evaluate(arg);
+                self.watch(p.watches.len(), arg, v);
+            },
+            "unwatch" => match arg.parse::<usize>() {
+                Ok(n) if n >= 1 && n <= p.watches.len() => {
+                    p.watches.remove(n - 1);
+                },
+                _ => {
+                    let _ = writeln!(out, "there is no watch {}", arg);
+                },
+            },
+            "l" => self.list(p.at.line, 5),
+            "h" | "help" => {
+                let _ = writeln!(out, "{}", HELP);
+            },
+            c => {
+                let _ = writeln!(out, "unknown command {}, try help", c);
+            },
+        }
+
+        None
+    }
+
+    fn watch(&mut self, n : usize, expr : &str, v : Result<Value, String>) -> () {
+        let _ = match v {
+            Ok(v) => writeln!(self.output, "watch {} : {} = {}", n, expr, v),
+            Err(e) => writeln!(self.output, "watch {} : {} : {}", n, expr, e),
+        };
+    }
+}
+
+impl Frontend for Console {
+    fn paused(&mut self, p : &mut Paused<'_>) -> Command {
+        let _ = writeln!(self.output, "paused at {} ({})", p.at, p.reason.name());
+        self.list(p.at.line, 0);
+        for (i, (w, v)) in p.watched().into_iter().enumerate() {
+            self.watch(i + 1, &w, v);
+        }
+
+        loop {
+            let _ = write!(self.output, "(debug) ");
+            let _ = self.output.flush();
+
+            let mut line = String::new();
+            match self.input.read_line(&mut line) {
+                Ok(0) | Err(_) => {
+                    let _ = writeln!(self.output);
+                    p.breakpoints.clear();
+                    return Command::Continue;
+                },
+                Ok(_) => {},
+            }
+
+            let mut line = String::from(line.trim());
+            if line.is_empty() {
+                line = self.last.clone();
+            }
+            self.last = line.clone();
+
+            if let Some(c) = self.command(p, &line) {
+                return c;
+            }
+        }
+    }
+}
+
+#[test]
+fn test_console() -> () {
+    use super::output::SharedBuffer;
+    use super::run::Runtime;
+
+    let text = "fn sq(x) [
+    y = x * x;
+    y;
+];
+a = sq(3);
+b = sq(a);
+displn(a, b);
+c = sq(2);
+displn(c);
+";
+
+    let commands = "w a\nn\ns\nbt\nvars\np x + 1\np x = 10\nl\no\nunwatch 1\nb 3\nc\np y\n\nvars 1\nbogus\nc\n";
+    let console = SharedBuffer::new();
+
+    let mut r = Runtime::new();
+    r.capture_output();
+    r.set_debugger(Debugger::new(Box::new(Console::new(text, Box::new(std::io::Cursor::new(commands)), Box::new(console.clone()))), BTreeSet::from([5]), false));
+
+    r.execute(&r.parse(text).unwrap()).unwrap();
+    assert_eq!(r.take_output(), "9\n100\n4\n");
+    assert_eq!(console.contents(), "\
+paused at 5:1 (breakpoint)
+>    5 | a = sq(3);
+(debug) watch 1 : a : name: Variable: a Not Found
+(debug) paused at 6:1 (step)
+>    6 | b = sq(a);
+watch 1 : a = 9
+(debug) paused at 2:5 (step)
+>    2 |     y = x * x;
+watch 1 : a = 9
+(debug) #0 sq at 2:5
+#1 main at 6:5
+(debug) x = 9
+(debug) 10
+(debug) nil
+(debug)      1 | fn sq(x) [
+>    2 |     y = x * x;
+     3 |     y;
+     4 | ];
+     5 | a = sq(3);
+     6 | b = sq(a);
+     7 | displn(a, b);
+(debug) paused at 7:1 (step)
+>    7 | displn(a, b);
+watch 1 : a = 9
+(debug) (debug) (debug) paused at 3:5 (breakpoint)
+>    3 |     y;
+(debug) 4
+(debug) 4
+(debug) a = 9
+b = 100
+(debug) unknown command bogus, try help
+(debug) ");
+}
@@ -1,20 +1,116 @@
 use std::collections::HashMap;
-use std::mem::transmute;
-
-use super::value::Value;
-use super::ast::Ast;
-use super::error::error;
+use std::io::Write;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::Instant;
+
+use super::value::{Value, Struct};
+use super::ast::{Ast, Span, Param};
+use super::error::{closest, error, format_trace, summarize, ErrorKind, Frame, RuntimeError};
+use super::limits::Limits;
+use super::builtins;
+use super::parser::Syntax;
+use super::debug::Debugger;
+use super::profile::Profiler;
 
 use rug::Integer;
+use regex::Regex;
+
+/// a function implemented in rust and registered by the host program. \
+/// receives its arguments in call order.
+pub type NativeFunction = Rc<dyn Fn(Vec<Value>) -> Value>;
+
+/// a function defined by a script
+pub struct Function {
+    pub params : Vec<Param>,
+    pub body : Ast,
+    /// the module it was defined in, None for the main program
+    pub namespace : Option<String>,
+}
+
+/// a `struct` declaration, calling it by name builds a Value::Struct
+pub struct StructType {
+    pub name : String,
+    pub fields : Vec<Param>,
+    /// the module it was declared in, defaults are evaluated there
+    pub namespace : Option<String>,
+}
 
 pub struct Environment {
     pub envs : Vec<HashMap<String, Value>>,
-    pub functions : HashMap<String, (Vec<String>, Ast)>,
+    /// functions of modules are stored as `module.name`
+    pub functions : HashMap<String, Rc<Function>>,
+    /// keyed like functions
+    pub structs : HashMap<String, Rc<StructType>>,
+    pub natives : HashMap<String, NativeFunction>,
+    /// program output (disp, displn), stdout by default
+    pub output : Box<dyn Write>,
+    /// error reports, stderr by default
+    pub diagnostics : Box<dyn Write>,
+
+    pub limits : Limits,
+    /// expressions evaluated since the last reset_counters
+    pub steps : u64,
+    /// nesting of script function calls
+    pub depth : usize,
+    pub deadline : Option<Instant>,
+
+    /// active script and native function calls, outermost first
+    pub frames : Vec<Frame>,
+
+    pub syntax : Rc<Syntax>,
+    /// module whose code is running, its globals are stored as `module.name` in envs[0]
+    pub namespace : Option<String>,
+    /// canonical path of every imported file to its module name
+    pub modules : HashMap<PathBuf, String>,
+    /// (importing module, alias) to module name
+    pub aliases : HashMap<(Option<String>, String), String>,
+    /// files being evaluated, the last one is the one imports are relative to
+    pub loading : Vec<PathBuf>,
+    pub search_paths : Vec<PathBuf>,
+    /// command line arguments after the script
+    pub args : Vec<String>,
+
+    /// compiled patterns of the regex builtins
+    pub regexes : HashMap<String, Regex>,
+
+    /// pauses the program at breakpoints and steps, see Environment::debug
+    pub debugger : Option<Box<Debugger>>,
+    /// times the calls and samples the stack, see profile::Profiler
+    pub profiler : Option<Box<Profiler>>,
+}
+
+impl Default for Environment {
+    fn default() -> Environment {
+        Environment::new()
+    }
 }
 
 impl Environment {
     pub fn new() -> Environment {
-        let mut ret = Environment { envs : Vec::new(), functions : HashMap::new() };
+        let mut ret = Environment {
+            envs : Vec::new(),
+            functions : HashMap::new(),
+            structs : HashMap::new(),
+            natives : HashMap::new(),
+            output : Box::new(std::io::stdout()),
+            diagnostics : Box::new(std::io::stderr()),
+            limits : Limits::new(),
+            steps : 0,
+            depth : 0,
+            deadline : None,
+            frames : Vec::new(),
+            syntax : Rc::new(Syntax::new()),
+            namespace : None,
+            modules : HashMap::new(),
+            aliases : HashMap::new(),
+            loading : Vec::new(),
+            search_paths : Vec::new(),
+            args : Vec::new(),
+            regexes : HashMap::new(),
+            debugger : None,
+            profiler : None,
+        };
         ret.push_env();
         return ret;
     }
@@ -27,9 +123,19 @@ impl Environment {
         self.envs.push(HashMap::new())
     }
 
+    /// the key of global `name` in envs[0] for the running module
+    pub fn global_key(&self, name : &String) -> String {
+        match &self.namespace {
+            Some(ns) => format!("{}.{}", ns, name),
+            None => name.clone(),
+        }
+    }
+
     pub fn push_val(&mut self, name : &String, val : Value) -> () {
-        if self.envs[0].contains_key(name) {
-            self.envs[0].insert(name.clone(), val);
+        let key = self.global_key(name);
+
+        if self.envs[0].contains_key(&key) || self.wenv() == 0 {
+            self.envs[0].insert(key, val);
         }
         else {
             let s = self.wenv();
@@ -38,157 +144,591 @@ impl Environment {
     }
 
     pub fn force_push(&mut self, name : &String, val : Value) -> () {
+        if self.wenv() == 0 {
+            let key = self.global_key(name);
+            self.envs[0].insert(key, val);
+            return;
+        }
+
         let c = self.wenv();
         self.envs[c].insert(name.clone(), val);
     }
 
-    pub fn push_function(&mut self, name : &String, args : &Vec<String>, val : &Ast) -> () {
-        self.functions.insert(name.clone(), (args.clone(), val.clone()));
+    /// force_push for each binding, returns what they shadowed so unbind can put it back
+    pub fn bind(&mut self, bound : Vec<(String, Value)>) -> Vec<(String, Option<Value>)> {
+        let mut shadowed = Vec::with_capacity(bound.len());
+
+        for (name, v) in bound {
+            let (c, key) = self.scope_key(&name);
+            shadowed.push((name, self.envs[c].insert(key, v)));
+        }
+
+        shadowed
     }
 
-    pub fn eval_function(&mut self, name : &String, mut args : Vec<Value>) -> Value {
-        args.reverse(); // there was a mistake in the grammar and it's too late now!
+    pub fn unbind(&mut self, shadowed : Vec<(String, Option<Value>)>) -> () {
+        // in reverse, a name bound twice gets its oldest value back
+        for (name, v) in shadowed.into_iter().rev() {
+            let (c, key) = self.scope_key(&name);
+            match v {
+                Some(v) => self.envs[c].insert(key, v),
+                None => self.envs[c].remove(&key),
+            };
+        }
+    }
+
+    /// where force_push stores `name`
+    fn scope_key(&self, name : &String) -> (usize, String) {
+        match self.wenv() {
+            0 => (0, self.global_key(name)),
+            c => (c, name.clone()),
+        }
+    }
+
+    pub fn push_function(&mut self, name : &String, params : &[Param], val : &Ast) -> Result<(), RuntimeError> {
+        check_name(name)?;
+        check_params(name, params)?;
+
+        if let Some(i) = params.iter().position(|p| p.rest) {
+            if i + 1 != params.len() {
+                return Err(RuntimeError::new(ErrorKind::Arity, format!("in {} : the rest parameter ...{} must be the last one", name, params[i].name)));
+            }
+        }
+
+        let f = Function { params : params.to_vec(), body : val.clone(), namespace : self.namespace.clone() };
+        self.functions.insert(self.global_key(name), Rc::new(f));
+        return Ok(());
+    }
+
+    pub fn push_struct(&mut self, name : &String, fields : &[Param]) -> Result<(), RuntimeError> {
+        check_name(name)?;
+        check_params(name, fields)?;
+
+        if let Some(p) = fields.iter().find(|p| p.rest) {
+            return Err(RuntimeError::new(ErrorKind::Arity, format!("in {} : a field can not be a rest parameter (...{})", name, p.name)));
+        }
+
+        let t = StructType { name : name.clone(), fields : fields.to_vec(), namespace : self.namespace.clone() };
+        self.structs.insert(self.global_key(name), Rc::new(t));
+        return Ok(());
+    }
+
+    /// calls `Name(...)` of a struct declaration, arguments are matched to fields like parameters
+    fn construct(&mut self, name : &String, t : &StructType, args : Vec<Value>, kwargs : Vec<(String, Value)>) -> Result<Value, RuntimeError> {
+        let slots = bind(name, &t.fields, args, kwargs)?;
+
+        self.push_env();
+        let caller = std::mem::replace(&mut self.namespace, t.namespace.clone());
+        let mut fields = Vec::with_capacity(slots.len());
+        let mut ret = Ok(());
+
+        for (p, v) in t.fields.iter().zip(slots) {
+            let v = match (v, &p.default) {
+                (Some(v), _) => v,
+                (None, Some(d)) => match d.eval(self) {
+                    Ok(v) => v,
+                    Err(e) => {
+                        ret = Err(e);
+                        break;
+                    },
+                },
+                (None, None) => {
+                    ret = Err(RuntimeError::new(ErrorKind::Arity, format!("{} is missing the field {}", name, p.name)));
+                    break;
+                },
+            };
+            self.force_push(&p.name, v.clone());
+            fields.push((p.name.clone(), v));
+        }
+
+        self.namespace = caller;
+        self.pop_env();
+        ret?;
+
+        return Ok(Value::Struct(Box::new(Struct { name : t.name.clone(), fields })));
+    }
+
+    /// the key in `functions` a call to `name` refers to. \
+    /// `alias.name` calls into an imported module, plain names stay inside the running module
+    pub fn function_key(&self, name : &String) -> Option<String> {
+        if let Some((alias, rest)) = name.split_once('.') {
+            let module = self.aliases.get(&(self.namespace.clone(), String::from(alias)))?;
+            return Some(format!("{}.{}", module, rest));
+        }
+
+        Some(self.global_key(name))
+    }
 
-        match name.as_str() {
+    pub fn push_native(&mut self, name : &str, f : NativeFunction) -> () {
+        self.natives.insert(name.to_string(), f);
+    }
+
+    pub fn set_output(&mut self, out : Box<dyn Write>) -> () {
+        self.output = out;
+    }
+
+    pub fn set_diagnostics(&mut self, out : Box<dyn Write>) -> () {
+        self.diagnostics = out;
+    }
+
+    pub fn write_out(&mut self, text : &str) -> () {
+        // a closed pipe should not bring the interpreter down
+        let _ = self.output.write_all(text.as_bytes());
+    }
+
+    /// reports `msg` on the diagnostic sink and evaluates to Nil
+    pub fn error(&mut self, msg : String) -> Value {
+        if !self.frames.is_empty() {
+            let trace = format_trace(&self.frames);
+            return error(&mut *self.diagnostics, format!("{}\n{}", msg, trace));
+        }
+        error(&mut *self.diagnostics, msg)
+    }
+
+    /// starts the step count and the clock of a new run
+    pub fn reset_counters(&mut self) -> () {
+        self.steps = 0;
+        self.deadline = self.limits.timeout.map(|t| Instant::now() + t);
+    }
+
+    /// enters a call, also for the profiler
+    fn push_frame(&mut self, frame : Frame) -> () {
+        if let Some(p) = &mut self.profiler {
+            p.enter(&frame.name);
+        }
+        self.frames.push(frame);
+    }
+
+    /// leaves the call of the last push_frame
+    fn pop_frame(&mut self) -> () {
+        if let Some(p) = &mut self.profiler {
+            p.exit();
+        }
+        self.frames.pop();
+    }
+
+    /// called once per evaluated expression
+    pub fn step(&mut self) -> Result<(), RuntimeError> {
+        self.steps += 1;
+
+        if let Some(max) = self.limits.max_steps {
+            if self.steps > max {
+                return Err(RuntimeError::new(ErrorKind::StepLimit, format!("exceeded the limit of {} evaluation steps", max)));
+            }
+        }
+
+        if let Some(p) = &mut self.profiler {
+            p.tick(&self.frames);
+        }
+
+        // looking at the clock on every step is noticeably slow
+        if self.steps.is_multiple_of(256) {
+            if let Some(deadline) = self.deadline {
+                if Instant::now() >= deadline {
+                    return Err(RuntimeError::new(ErrorKind::Timeout, format!("exceeded the time limit of {:?}", self.limits.timeout.unwrap_or_default())));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// passes v through unless it is a list or string above the size limit
+    pub fn check_size(&self, v : Value) -> Result<Value, RuntimeError> {
+        let Some(max) = self.limits.max_size else {return Ok(v);};
+
+        let size = match &v {
+            Value::List(l) => l.len(),
+            Value::Set(s) => s.len(),
+            Value::String(s) => s.len(),
+            _ => 0,
+        };
+
+        if size > max {
+            return Err(RuntimeError::new(ErrorKind::MemoryLimit, format!("value of size {} exceeds the limit of {}", size, max)));
+        }
+
+        Ok(v)
+    }
+
+    /// the builtins of BUILTINS, args are in call order
+    fn core(&mut self, name : &str, mut args : Vec<Value>) -> Result<Value, RuntimeError> {
+        match name {
             "disp" => {
                 for i in args {
-                    print!("{}", i);
+                    self.write_out(&format!("{}", i));
                 }
-                return Value::Nil;
+                return Ok(Value::Nil);
             },
             "displn" => {
                 for i in args {
-                    println!("{}", i);
+                    self.write_out(&format!("{}\n", i));
                 }
-                return Value::Nil;
+                return Ok(Value::Nil);
             },
             "pop" => {
                 if args.len() != 2 {
-                    return error(format!("Calls to pop should have 2 arguments : a vector followed by a number"));
+                    return Err(RuntimeError::new(ErrorKind::Arity, format!("Calls to pop should have 2 arguments : a vector followed by a number")));
                 }
 
                 let t = args[1].take().as_integer();
 
                 if !args[0].is_vec() || !t.is_integer() {
-                    println!("{}", args[0]);
-                    return error(format!("Calls to pop should have 2 arguments : a vector followed by a number"));
+                    return Err(RuntimeError::new(ErrorKind::Type, format!("Calls to pop should have 2 arguments : a vector followed by a number")));
                 }
 
                 let Value::List(mut l) = args[0].take() else {unreachable!()};
 
-                l.remove(t.force_integer().unwrap().to_usize_wrapping());
+                let i = t.force_integer().unwrap().to_usize_wrapping();
+                if i >= l.len() {
+                    return Err(RuntimeError::new(ErrorKind::Index, format!("pop called with out of bounds index")));
+                }
+                l.remove(i);
 
-                return Value::List(l);
+                return Ok(Value::List(l));
             },
             "get" => {
                 if args.len() != 2 {
-                    return error(format!("Calls to get should have 2 arguments : a vector followed by a number"));
+                    return Err(RuntimeError::new(ErrorKind::Arity, format!("Calls to get should have 2 arguments : a vector followed by a number")));
                 }
 
                 let t = args[1].take().as_integer();
 
                 if !args[0].is_vec() || !t.is_integer() {
-                    return error(format!("Calls to get should have 2 arguments : a vector followed by a number"));
+                    return Err(RuntimeError::new(ErrorKind::Type, format!("Calls to get should have 2 arguments : a vector followed by a number")));
                 }
 
                 let Value::List(l) = args[0].take() else {unreachable!()};
 
                 return match l.get(t.force_integer().unwrap().to_usize_wrapping()) {
-                    Some(x) => x.clone(),
-                    None => error(format!("get called with out of bounds index")),
+                    Some(x) => Ok(x.clone()),
+                    None => Err(RuntimeError::new(ErrorKind::Index, format!("get called with out of bounds index"))),
                 };
             },
             "dim" => {
                 if args.len() != 1 {
-                    return error(format!("Calls to dim should have ONE arguments"));
+                    return Err(RuntimeError::new(ErrorKind::Arity, format!("Calls to dim should have ONE arguments")));
                 }
 
-                return match args[0].take() {
+                return Ok(match args[0].take() {
                     Value::Float(_) => Value::Integer(Integer::new()),
                     Value::Integer(_) => Value::Integer(Integer::new()),
                     Value::Bool(_) => Value::Integer(Integer::new()),
-                    Value::String(s) => Value::Integer(Integer::from(s.len())),
+                    Value::String(s) => Value::Integer(Integer::from(s.chars().count())),
                     Value::List(l) => Value::Integer(Integer::from(l.len())),
+                    Value::Set(s) => Value::Integer(Integer::from(s.len())),
+                    Value::Function(_) => Value::Integer(Integer::new()),
+                    Value::Error(_) => Value::Integer(Integer::new()),
+                    Value::Struct(s) => Value::Integer(Integer::from(s.fields.len())),
                     Value::Nil => Value::Integer(Integer::new()),
-                };
+                });
             },
             "vec" => {
                 if args.len() != 1 {
-                    return error(format!("Calls to vec should have ONE arguments"));
+                    return Err(RuntimeError::new(ErrorKind::Arity, format!("Calls to vec should have ONE arguments")));
                 }
 
-                return args[0].take().as_lst();
+                return Ok(args[0].take().as_lst());
             },
             "str" => {
                 if args.len() != 1 {
-                    return error(format!("Calls to str should have ONE arguments"));
+                    return Err(RuntimeError::new(ErrorKind::Arity, format!("Calls to str should have ONE arguments")));
                 }
 
-                return args[0].take().as_str();
+                return Ok(args[0].take().as_str());
             },
             "int" => {
                 if args.len() != 1 {
-                    return error(format!("Calls to int should have ONE arguments"));
+                    return Err(RuntimeError::new(ErrorKind::Arity, format!("Calls to int should have ONE arguments")));
                 }
 
-                return args[0].take().as_integer();
+                return Ok(args[0].take().as_integer());
             },
             "nil" => {
                 if args.len() != 1 {
-                    return error(format!("Calls to nil should have ONE arguments"));
+                    return Err(RuntimeError::new(ErrorKind::Arity, format!("Calls to nil should have ONE arguments")));
                 }
 
-                return match args[0].take() {
+                return Ok(match args[0].take() {
                     Value::Nil => Value::Bool(false),
                     _ => Value::Bool(true),
+                });
+            },
+            "set" => {
+                if args.len() != 1 {
+                    return Err(RuntimeError::new(ErrorKind::Arity, format!("Calls to set should have ONE arguments")));
+                }
+
+                return match args[0].take() {
+                    Value::List(l) => Value::set_of(l),
+                    s @ Value::Set(_) => Ok(s),
+                    v => Err(RuntimeError::new(ErrorKind::Type, format!("Calls to set take a list, found {}", v.type_name()))),
                 };
             },
-            _ => {},
-        };
+            "type_of" => {
+                if args.len() != 1 {
+                    return Err(RuntimeError::new(ErrorKind::Arity, format!("Calls to type_of should have ONE arguments")));
+                }
 
-        
-        if self.functions.len() + 128 >= self.functions.capacity() {
-            // such a hack :/
-            self.functions.reserve(256);
+                return Ok(Value::String(args[0].type_name()));
+            },
+            _ => Err(RuntimeError::new(ErrorKind::Name, format!("{} is not one of the core builtins", name))),
         }
-        
-        self.push_env();
+    }
 
-        let Some((names, body)) = self.functions.get(name) else {return Value::Nil;};
-        
-        let names = names as *const Vec<String>;
-        let body = body as *const Ast;
-        
-        // this is unsafe iff the source of functions moves  -- if a function is defined with the same name OR 128 to 256 functions are added
-        // hopefully that doesn't happen
-        let names = unsafe { transmute::<*const Vec<String>, &Vec<String>>(names) };
-        let body = unsafe { transmute::<*const Ast, &Ast>(body) };
+    /// `at` is the call site, used for stack traces
+    /// `args` come in the grammar's (reversed) order, `kwargs` in call order
+    pub fn eval_function(&mut self, name : &String, mut args : Vec<Value>, kwargs : Vec<(String, Value)>, at : Span) -> Result<Value, RuntimeError> {
+        args.reverse(); // there was a mistake in the grammar and it's too late now!
+
+        if !kwargs.is_empty() && (BUILTINS.contains(&name.as_str()) || builtins::get(name).is_some() || self.natives.contains_key(name)) {
+            return Err(RuntimeError::new(ErrorKind::Arity, format!("{} does not take keyword arguments", name)));
+        }
+
+        if BUILTINS.contains(&name.as_str()) {
+            let summary = args.iter().map(summarize).collect::<Vec<String>>().join(", ");
+
+            self.push_frame(Frame { name : name.clone(), args : summary, call_site : at });
+            let ret = self.core(name, args);
+            self.pop_frame();
+
+            return ret;
+        }
+
+        if let Some(f) = builtins::get(name) {
+            let summary = args.iter().map(summarize).collect::<Vec<String>>().join(", ");
+
+            self.push_frame(Frame { name : name.clone(), args : summary, call_site : at });
+            let ret = f(self, args);
+            self.pop_frame();
+
+            return ret;
+        }
+
+        if let Some(f) = self.natives.get(name) {
+            let f = f.clone();
+            let summary = args.iter().map(summarize).collect::<Vec<String>>().join(", ");
+
+            self.push_frame(Frame { name : name.clone(), args : summary, call_site : at });
+            let ret = f(args);
+            self.pop_frame();
+
+            return Ok(ret);
+        }
         
+        if let Some(t) = self.function_key(name).and_then(|k| self.structs.get(&k).cloned()) {
+            return self.construct(name, &t, args, kwargs);
+        }
 
-        for (s, v) in names.into_iter().zip(args) {
-            self.force_push(s, v);
+        // the Rc keeps the body alive even if the function is redefined while it runs
+        let Some(function) = self.function_key(name).and_then(|k| self.functions.get(&k).cloned()) else {
+            let message = match self.suggestion(name) {
+                Some(s) => format!("call to undefined function {}, did you mean {}?", name, s),
+                None => format!("call to undefined function {}", name),
+            };
+            return Err(RuntimeError::new(ErrorKind::Name, message));
+        };
+        let body = &function.body;
+
+        if self.depth >= self.limits.max_depth {
+            return Err(RuntimeError::new(ErrorKind::StackOverflow, format!("calls nested deeper than {} (in {})", self.limits.max_depth, name)));
         }
 
-        let ret = body.eval(self);
+        // every return from here on pops it
+        self.push_env();
+
+        let slots = match bind(name, &function.params, args, kwargs) {
+            Ok(x) => x,
+            Err(e) => {
+                self.pop_env();
+                return Err(e);
+            },
+        };
+
+        let summary = function.params.iter().zip(&slots)
+            .filter_map(|(p, v)| v.as_ref().map(|v| format!("{}={}", p.name, summarize(v))))
+            .collect::<Vec<String>>();
+
+        self.push_frame(Frame { name : name.clone(), args : summary.join(", "), call_site : at });
+        self.depth += 1;
+        let caller = std::mem::replace(&mut self.namespace, function.namespace.clone());
+        // recursion continues on a fresh heap allocated stack instead of overflowing the native one,
+        // max_depth is what bounds it
+        let mut ret = stacker::maybe_grow(256 * 1024, 4 * 1024 * 1024, || {
+            // defaults are evaluated in the callee's frame, so they can refer to earlier parameters
+            for (p, v) in function.params.iter().zip(slots) {
+                let v = match (v, &p.default) {
+                    (Some(v), _) => v,
+                    (None, Some(d)) => d.eval(self)?,
+                    (None, None) => return Err(RuntimeError::new(ErrorKind::Arity, format!("{} is missing the argument {}", name, p.name))),
+                };
+                self.force_push(&p.name, v);
+            }
+
+            body.eval(self)
+        });
+        self.namespace = caller;
+        self.depth -= 1;
+
+        // the innermost frame records the whole stack, the outer ones leave it alone
+        if let Err(e) = &mut ret {
+            if e.trace.is_empty() {
+                e.trace = self.frames.clone();
+            }
+        }
 
+        self.pop_frame();
         self.pop_env();
 
         return ret;
     }
 
     pub fn fetch(&self, name : &String) -> Option<Value> {
-        match self.envs[self.wenv()].get(name) {
-            Some(x) => {return Some(x.clone());},
-            None => {},
+        if self.wenv() > 0 {
+            if let Some(x) = self.envs[self.wenv()].get(name) {
+                return Some(x.clone());
+            }
+        }
+
+        return self.envs[0].get(&self.global_key(name)).cloned();
+    }
+
+    /// whether a call to `name` would find a builtin, native, function or struct
+    pub fn is_callable(&self, name : &String) -> bool {
+        if BUILTINS.contains(&name.as_str()) || builtins::get(name).is_some() || self.natives.contains_key(name) {
+            return true;
+        }
+
+        match self.function_key(name) {
+            Some(k) => self.functions.contains_key(&k) || self.structs.contains_key(&k),
+            None => false,
+        }
+    }
+
+    /// the callable name closest to `name` that a call from the running module can reach
+    fn suggestion(&self, name : &str) -> Option<String> {
+        // `alias.f` is looked for among the functions of that module
+        let (shown, key) = match name.split_once('.') {
+            Some((alias, _)) => {
+                let module = self.aliases.get(&(self.namespace.clone(), String::from(alias)))?;
+                (format!("{}.", alias), format!("{}.", module))
+            },
+            None => (String::new(), self.global_key(&String::new())),
         };
 
-        return match self.envs[0].get(name) {
-            Some(x) => Some(x.clone()),
-            None => None,
+        let mut candidates : Vec<String> = self.functions.keys().chain(self.structs.keys())
+            .filter_map(|k| k.strip_prefix(key.as_str()))
+            .filter(|k| !k.contains('.'))
+            .map(|k| format!("{}{}", shown, k))
+            .collect();
+
+        if shown.is_empty() {
+            candidates.extend(BUILTINS.iter().copied().chain(builtins::names()).map(String::from));
+            candidates.extend(self.natives.keys().cloned());
+        }
+
+        return closest(name, candidates.iter().map(|c| c.as_str())).map(String::from);
+    }
+
+    /// compiles `pattern` once per Environment
+    pub fn regex(&mut self, pattern : &String) -> Result<Regex, RuntimeError> {
+        if let Some(r) = self.regexes.get(pattern) {
+            return Ok(r.clone());
+        }
+
+        let r = match Regex::new(pattern) {
+            Ok(r) => r,
+            Err(e) => return Err(RuntimeError::new(ErrorKind::Parse, format!("invalid regex {:?} : {}", pattern, e))),
+        };
+
+        // patterns built at runtime could grow the cache forever
+        if self.regexes.len() >= REGEX_CACHE {
+            self.regexes.clear();
+        }
+
+        self.regexes.insert(pattern.clone(), r.clone());
+        return Ok(r);
+    }
+
+    /// calls a Value::Function with arguments in their natural order, used by builtins taking callbacks
+    pub fn call_value(&mut self, f : &Value, mut args : Vec<Value>) -> Result<Value, RuntimeError> {
+        let Value::Function(name) = f else {
+            return Err(RuntimeError::new(ErrorKind::Type, format!("{} is not a function", f)));
         };
+
+        // errors inside the callback point at the builtin that called it
+        let at = self.frames.last().map(|f| f.call_site).unwrap_or_default();
+
+        args.reverse(); // eval_function expects the grammar's argument order
+        return self.eval_function(name, args, vec![], at);
     }
 
     pub fn pop_env(&mut self) -> () {
         self.envs.pop();
     }
-}
\ No newline at end of file
+}
+
+const REGEX_CACHE : usize = 256;
+
+/// the functions handled directly by `eval_function`
+pub const BUILTINS : [&str; 11] = ["disp", "displn", "pop", "get", "dim", "vec", "str", "int", "nil", "type_of", "set"];
+
+/// whether `name` is one of BUILTINS or of the builtins module
+pub fn is_builtin(name : &str) -> bool {
+    BUILTINS.contains(&name) || builtins::get(name).is_some()
+}
+
+/// calls go to the builtin first, a function or struct of the same name could never be called
+fn check_name(name : &str) -> Result<(), RuntimeError> {
+    if is_builtin(name) {
+        return Err(RuntimeError::new(ErrorKind::Name, format!("{} is a builtin and can not be redefined", name)));
+    }
+
+    Ok(())
+}
+
+/// parameter and field names must be unique
+fn check_params(name : &str, params : &[Param]) -> Result<(), RuntimeError> {
+    for (i, p) in params.iter().enumerate() {
+        if params[..i].iter().any(|q| q.name == p.name) {
+            return Err(RuntimeError::new(ErrorKind::Arity, format!("in {} : duplicate parameter {}", name, p.name)));
+        }
+    }
+
+    Ok(())
+}
+
+/// matches the arguments of a call to the parameters of `name`. \
+/// a parameter left as None takes its default
+fn bind(name : &str, params : &[Param], args : Vec<Value>, kwargs : Vec<(String, Value)>) -> Result<Vec<Option<Value>>, RuntimeError> {
+    let mut slots : Vec<Option<Value>> = params.iter().map(|_| None).collect();
+    let rest = params.iter().position(|p| p.rest);
+    let positional = rest.unwrap_or(params.len());
+
+    if args.len() > positional && rest.is_none() {
+        return Err(RuntimeError::new(ErrorKind::Arity, format!("{} takes at most {} arguments but {} were given", name, positional, args.len())));
+    }
+
+    let mut args = args.into_iter();
+    for slot in slots.iter_mut().take(positional) {
+        *slot = args.next();
+    }
+
+    if let Some(i) = rest {
+        slots[i] = Some(Value::List(args.collect()));
+    }
+
+    for (k, v) in kwargs {
+        let Some(i) = params.iter().position(|p| p.name == k && !p.rest) else {
+            return Err(RuntimeError::new(ErrorKind::Arity, format!("{} has no parameter named {}", name, k)));
+        };
+
+        if slots[i].is_some() {
+            return Err(RuntimeError::new(ErrorKind::Arity, format!("{} got multiple values for the argument {}", name, k)));
+        }
+
+        slots[i] = Some(v);
+    }
+
+    return Ok(slots);
+}
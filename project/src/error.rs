@@ -1,7 +1,242 @@
 use super::value::Value;
+use super::ast::Span;
 use colored::*;
 
-pub fn error(msg : String) -> Value {
-    println!("ERR: {}", msg.blink().red());
+use std::io::Write;
+
+/// reports `msg` on the diagnostic sink and evaluates to Nil
+pub fn error(out : &mut dyn Write, msg : String) -> Value {
+    // a diagnostic that can not be written has nowhere left to go
+    let _ = writeln!(out, "ERR: {}", msg.blink().red());
     Value::Nil
-}
\ No newline at end of file
+}
+
+/// one active function call
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub name : String,
+    /// short rendering of the arguments, eg `x=3, l=[1,2,...`
+    pub args : String,
+    /// where the call was made, zero for calls from the host program
+    pub call_site : Span,
+}
+
+impl std::fmt::Display for Frame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}({})", self.name, self.args)?;
+        if self.call_site != Span::default() {
+            write!(f, " called at {}", self.call_site)?;
+        }
+        Ok(())
+    }
+}
+
+/// renders a value for a stack trace, long values are cut off
+pub fn summarize(v : &Value) -> String {
+    const MAX : usize = 24;
+
+    let s = match v {
+        Value::String(s) => format!("{:?}", s),
+        _ => format!("{}", v),
+    };
+
+    if s.chars().count() <= MAX {
+        return s;
+    }
+
+    let mut cut : String = s.chars().take(MAX).collect();
+    cut.push_str("...");
+    cut
+}
+
+/// the candidate closest to `name` by edit distance, None when even that one is too different to be a typo
+pub fn closest<'a>(name : &str, candidates : impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    let limit = (name.chars().count() / 3).max(1);
+
+    // ties go to the longest common prefix, then to the alphabetically first one so suggestions do not depend on hash order
+    return candidates.filter(|c| *c != name)
+        .map(|c| (distance(name, c), std::cmp::Reverse(name.chars().zip(c.chars()).take_while(|(x, y)| x == y).count()), c))
+        .filter(|(d, _, _)| *d <= limit)
+        .min()
+        .map(|(_, _, c)| c);
+}
+
+/// the number of characters to insert, delete or replace to turn a into b
+fn distance(a : &str, b : &str) -> usize {
+    let b : Vec<char> = b.chars().collect();
+    let mut row : Vec<usize> = (0..=b.len()).collect();
+
+    for (i, x) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+
+        for (j, y) in b.iter().enumerate() {
+            let replace = diagonal + if x == *y { 0 } else { 1 };
+            diagonal = row[j + 1];
+            row[j + 1] = replace.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+
+    return row[b.len()];
+}
+
+/// most recent call first. runs of the same function (recursion) are collapsed into one line
+pub fn format_trace(frames : &[Frame]) -> String {
+    let mut ret = String::from("stack trace (most recent call first):");
+    let mut i = frames.len();
+
+    while i > 0 {
+        let top = &frames[i - 1];
+        let mut run = 1;
+        while run < i && frames[i - 1 - run].name == top.name {
+            run += 1;
+        }
+
+        if run <= 3 {
+            for f in frames[i - run..i].iter().rev() {
+                ret.push_str(&format!("\n    {}", f));
+            }
+        }
+        else {
+            ret.push_str(&format!("\n    {}", top));
+            ret.push_str(&format!("\n    {} ... (repeated {} times)", top.name, run - 1));
+        }
+
+        i -= run;
+    }
+
+    ret
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    /// operands or arguments of the wrong type
+    Type,
+    DivisionByZero,
+    /// index out of bounds
+    Index,
+    /// wrong number of arguments
+    Arity,
+    /// unbound variable or undefined function
+    Name,
+    /// raised by `throw`
+    Thrown,
+    /// a module that can not be found, read or parsed, or an import cycle
+    Import,
+    /// a `match` without a matching arm
+    Match,
+    /// text that is not a valid number, see parse_int and parse_float
+    Parse,
+    /// a program that can not be parsed, see Runtime::run
+    Syntax,
+
+    StepLimit,
+    StackOverflow,
+    MemoryLimit,
+    Timeout,
+
+    /// raised by `exit(code)`, stops the program with that status
+    Exit,
+}
+
+impl ErrorKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ErrorKind::Type => "type",
+            ErrorKind::DivisionByZero => "division by zero",
+            ErrorKind::Index => "index",
+            ErrorKind::Arity => "arity",
+            ErrorKind::Name => "name",
+            ErrorKind::Thrown => "thrown",
+            ErrorKind::Import => "import",
+            ErrorKind::Match => "no match",
+            ErrorKind::Parse => "parse",
+            ErrorKind::Syntax => "syntax",
+            ErrorKind::StepLimit => "step limit",
+            ErrorKind::StackOverflow => "stack overflow",
+            ErrorKind::MemoryLimit => "memory limit",
+            ErrorKind::Timeout => "timeout",
+            ErrorKind::Exit => "exit",
+        }
+    }
+
+    /// the resource limits can not be caught, otherwise a script could simply ignore them. \
+    /// neither can exit, it is not an error
+    pub fn catchable(&self) -> bool {
+        !matches!(self, ErrorKind::StepLimit | ErrorKind::MemoryLimit | ErrorKind::Timeout | ErrorKind::Exit)
+    }
+}
+
+/// an error that aborts evaluation, unlike the ones reported through `error`
+#[derive(Debug, Clone)]
+pub struct RuntimeError {
+    pub kind : ErrorKind,
+    pub message : String,
+    /// the calls that were active where the error happened, outermost first
+    pub trace : Vec<Frame>,
+    /// what was thrown, for errors raised by `throw`
+    pub value : Option<Box<Value>>,
+    /// where a syntax error is
+    pub at : Option<Span>,
+}
+
+impl RuntimeError {
+    pub fn new(kind : ErrorKind, message : String) -> RuntimeError {
+        RuntimeError { kind, message, trace : Vec::new(), value : None, at : None }
+    }
+
+    /// a program that does not parse, `at` is where it stops being valid
+    pub fn syntax(message : String, at : Option<Span>) -> RuntimeError {
+        RuntimeError { kind : ErrorKind::Syntax, message, trace : Vec::new(), value : None, at }
+    }
+
+    /// the error raised by `throw v`
+    pub fn thrown(v : Value) -> RuntimeError {
+        RuntimeError { kind : ErrorKind::Thrown, message : format!("{}", v), trace : Vec::new(), value : Some(Box::new(v)), at : None }
+    }
+
+    /// the error raised by `exit(code)`
+    pub fn exit(code : i32) -> RuntimeError {
+        RuntimeError { kind : ErrorKind::Exit, message : format!("status {}", code), trace : Vec::new(), value : Some(Box::new(Value::Integer(code.into()))), at : None }
+    }
+
+    /// the status a program that raised this error should exit with
+    pub fn exit_code(&self) -> i32 {
+        match (self.kind, self.value.as_deref()) {
+            (ErrorKind::Exit, Some(Value::Integer(code))) => code.to_i32().unwrap_or(1),
+            _ => 1,
+        }
+    }
+}
+
+impl std::fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.kind.name(), self.message)?;
+        if let Some(at) = self.at {
+            write!(f, " at {}:{}", at.line, at.column)?;
+        }
+        if !self.trace.is_empty() {
+            write!(f, "\n{}", format_trace(&self.trace))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for RuntimeError {}
+
+#[test]
+fn test_trace() -> () {
+    let frame = |name : &str, line| Frame { name : String::from(name), args : String::from("x=1"), call_site : Span { line, column : 1 } };
+
+    let mut frames = vec![frame("main", 1)];
+    for _ in 0..251 {
+        frames.push(frame("fib", 2));
+    }
+    frames.push(frame("helper", 3));
+
+    assert_eq!(format_trace(&frames), "stack trace (most recent call first):
+    helper(x=1) called at 3:1
+    fib(x=1) called at 2:1
+    fib ... (repeated 250 times)
+    main(x=1) called at 1:1");
+}
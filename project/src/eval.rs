@@ -1,39 +1,58 @@
 #![allow(unused)]
 
-use super::ast::{Ast, Forkop};
+use std::cmp::Ordering;
+
+use super::ast::{Ast, Forkop, Pattern};
 use super::value::Value;
 use super::environment::Environment;
-use super::error::error;
+use super::error::{summarize, ErrorKind, RuntimeError};
 
 impl Ast {
-    pub fn eval(&self, env : &mut Environment) -> Value {
-        match self {
+    pub fn eval(&self, env : &mut Environment) -> Result<Value, RuntimeError> {
+        env.step()?;
+
+        if env.debugger.is_some() {
+            if let Some(at) = self.span() {
+                env.debug(at)?;
+            }
+        }
+
+        let v = match self {
             Ast::Literal(l) => l.clone(),
-            Ast::Get(g) => match env.fetch(g) {
+            Ast::Get(g, _) => match env.fetch(g) {
                 Some(x) => x,
-                None => error(format!("Variable: {} Not Found", g)),
+                // a function used as a value, `map(xs, double)`
+                None if env.is_callable(g) => Value::Function(g.clone()),
+                None => return Err(RuntimeError::new(ErrorKind::Name, format!("Variable: {} Not Found", g))),
             },
             Ast::VecLiteral(v) => {
                 let mut ret = Vec::with_capacity(v.len());
                 for i in v {
-                    ret.push(i.eval(env));
+                    ret.push(i.eval(env)?);
+                }
+                env.check_size(Value::List(ret))?
+            },
+            Ast::SetLiteral(v) => {
+                let mut ret = Vec::with_capacity(v.len());
+                for i in v {
+                    ret.push(i.eval(env)?);
                 }
-                Value::List(ret)
+                env.check_size(Value::set_of(ret)?)?
             },
-            Ast::FunDef { name, args, body } => {
-                env.push_function(name, args, body);
+            Ast::FunDef { name, args, body, .. } => {
+                env.push_function(name, args, body)?;
                 Value::Nil
             },
-            Ast::Set { name, value } => {
-                let e = value.eval(env);
+            Ast::Set { name, value, .. } => {
+                let e = value.eval(env)?;
                 env.push_val(name, e);
                 Value::Nil
             },
             Ast::While { cond, body } => {
                 let mut v = Value::Nil;
 
-                while cond.eval(env).force_bool() {
-                    v = body.eval(env);
+                while cond.eval_bool(env)? {
+                    v = body.eval(env)?;
                 }
 
                 v
@@ -42,50 +61,328 @@ impl Ast {
                 let mut v = Value::Nil;
 
                 for i in e {
-                    v = i.eval(env);
+                    v = i.eval(env)?;
                 }
 
                 v
             },
-            Ast::Call { name, with } => {
+            Ast::Call { name, with, kwargs, span } => {
                 let mut evals = Vec::with_capacity(with.len());
 
                 for i in with {
-                    evals.push(i.eval(env));
+                    evals.push(i.eval(env)?);
                 }
 
-                env.eval_function(name, evals)
+                let mut named = Vec::with_capacity(kwargs.len());
+
+                for (k, i) in kwargs {
+                    named.push((k.clone(), i.eval(env)?));
+                }
+
+                // a variable holding a function value is called through
+                let v = match env.fetch(name) {
+                    Some(Value::Function(f)) => env.eval_function(&f, evals, named, *span)?,
+                    _ => env.eval_function(name, evals, named, *span)?,
+                };
+                env.check_size(v)?
             },
-            Ast::If { if_, then, else_ } => {
-                if if_.eval(env).force_bool() {
+            Ast::If { if_, then, else_, .. } => {
+                if if_.eval_bool(env)? {
                     return then.eval(env);
                 }
 
                 match else_ {
-                    Some(x) => x.eval(env),
+                    Some(x) => x.eval(env)?,
                     None => Value::Nil,
                 }
             },
             Ast::Fork { left, right, op } => {
-                let left = left.eval(env);
-                let right = right.eval(env);
-
-                match op {
-                    Forkop::Gt  => {match left.comp(right) { Some (x) => Value::Bool(x > 0), None => error(format!("Invalid Comparison!"))}},
-                    Forkop::Lt  => {match left.comp(right) { Some (x) => Value::Bool(x < 0), None => error(format!("Invalid Comparison!"))}},
-                    Forkop::Lte => {match left.comp(right) { Some (x) => Value::Bool(x <= 0), None => error(format!("Invalid Comparison!"))}},
-                    Forkop::Gte => {match left.comp(right) { Some (x) => Value::Bool(x >= 0), None => error(format!("Invalid Comparison!"))}},
-                    Forkop::Eql => {match left.comp(right) { Some (x) => Value::Bool(x == 0), None => error(format!("Invalid Comparison!"))}},
-                    Forkop::Neq => {match left.comp(right) { Some (x) => Value::Bool(x != 0), None => error(format!("Invalid Comparison!"))}},
-                    
-                    Forkop::Add => {left.add(right) },
-                    Forkop::Sub => {left.sub(right) },
-                    Forkop::Mul => {left.mul(right) },
-                    Forkop::Div => {left.div(right) },
-                    Forkop::Pow => {left.pow(right) },
-                    Forkop::Mod => {left.mod_(right)},
+                let left = left.eval(env)?;
+                let right = right.eval(env)?;
+
+                let v = binary(op, left, right)?;
+
+                env.check_size(v)?
+            },
+            Ast::Throw(e) => {
+                return match e.eval(env)? {
+                    Value::Error(err) => Err(*err), // rethrow
+                    v => Err(RuntimeError::thrown(v)),
+                };
+            },
+            Ast::Try { body, name, handler } => {
+                match body.eval(env) {
+                    Ok(v) => v,
+                    Err(mut e) if e.kind.catchable() => {
+                        // an error that did not leave a function has not recorded where it happened yet
+                        if e.trace.is_empty() {
+                            e.trace = env.frames.clone();
+                        }
+                        // the error is only bound in the handler
+                        let shadowed = env.bind(vec![(name.clone(), Value::Error(Box::new(e)))]);
+                        let ret = handler.eval(env);
+                        env.unbind(shadowed);
+                        ret?
+                    },
+                    Err(e) => return Err(e),
                 }
             },
-        }
+            Ast::Field { target, field } => {
+                // a module alias is not a variable
+                if let Ast::Get(alias, _) = &**target {
+                    if env.fetch(alias).is_none() {
+                        if let Some(v) = env.module_global(alias, field) {
+                            return v;
+                        }
+                    }
+                }
+
+                let t = target.eval(env)?;
+                t.field(field)?
+            },
+            Ast::Import { path, alias } => env.import(path, alias)?,
+            Ast::StructDef { name, fields, .. } => {
+                env.push_struct(name, fields)?;
+                Value::Nil
+            },
+            Ast::Match { value, arms } => {
+                let v = value.eval(env)?;
+
+                for arm in arms {
+                    let mut bound = Vec::new();
+                    if !matches(&arm.pattern, &v, &mut bound) {
+                        continue;
+                    }
+
+                    // the bindings only exist in the guard and the body of their arm
+                    let shadowed = env.bind(bound);
+
+                    let taken = match &arm.guard {
+                        Some(guard) => guard.eval_bool(env),
+                        None => Ok(true),
+                    };
+                    let ret = match taken {
+                        Ok(true) => Some(arm.body.eval(env)),
+                        Ok(false) => None,
+                        Err(e) => Some(Err(e)),
+                    };
+
+                    env.unbind(shadowed);
+
+                    if let Some(ret) = ret {
+                        return ret;
+                    }
+                }
+
+                return Err(RuntimeError::new(ErrorKind::Match, format!("no pattern matched {}", summarize(&v))));
+            },
+            Ast::Destructure { pattern, value } => {
+                let v = value.eval(env)?;
+                let mut bound = Vec::new();
+
+                if !matches(pattern, &v, &mut bound) {
+                    return Err(RuntimeError::new(ErrorKind::Match, format!("cannot destructure {} into {}", summarize(&v), pattern)));
+                }
+
+                for (name, x) in bound {
+                    env.push_val(&name, x);
+                }
+
+                Value::Nil
+            },
+            Ast::Index { target, index } => {
+                let i = index.eval(env)?;
+                target.eval(env)?.index(&i)?
+            },
+            Ast::SetIndex { target, index, value } => {
+                let v = value.eval(env)?;
+                let mut steps = Vec::new();
+                let name = place(env, target, &mut steps)?;
+                steps.push(Step::Index(index.eval(env)?));
+                store(env, name, &steps, v)?;
+                Value::Nil
+            },
+            Ast::Update { target, op, value } => {
+                let v = value.eval(env)?;
+
+                // the place is only evaluated once, `a[f()][g()] += 1` calls f and g once
+                let mut steps = Vec::new();
+                let name = place(env, target, &mut steps)?;
+                let Some(root) = env.fetch(name) else {
+                    return Err(RuntimeError::new(ErrorKind::Name, format!("Variable: {} Not Found", name)));
+                };
+                let new = env.check_size(binary(op, load(root, &steps)?, v)?)?;
+                store(env, name, &steps, new)?;
+
+                Value::Nil
+            },
+            Ast::SetField { target, field, value } => {
+                let v = value.eval(env)?;
+                let mut steps = Vec::new();
+                let name = place(env, target, &mut steps)?;
+                steps.push(Step::Field(field.clone()));
+                store(env, name, &steps, v)?;
+                Value::Nil
+            },
+        };
+
+        Ok(v)
     }
-}
\ No newline at end of file
+
+    pub fn eval_bool(&self, env : &mut Environment) -> Result<bool, RuntimeError> {
+        self.eval(env)?.force_bool()
+    }
+}
+
+/// `left op right`
+fn binary(op : &Forkop, left : Value, right : Value) -> Result<Value, RuntimeError> {
+    let v = match op {
+        Forkop::Eql => Value::Bool(left == right),
+        Forkop::Neq => Value::Bool(left != right),
+        Forkop::Gt | Forkop::Lt | Forkop::Lte | Forkop::Gte => {
+            let Some(o) = left.partial_cmp(&right) else {
+                return Err(RuntimeError::new(ErrorKind::Type, format!("Invalid Comparison! ({} and {} are not ordered)", left.type_name(), right.type_name())));
+            };
+
+            Value::Bool(match op {
+                Forkop::Gt => o == Ordering::Greater,
+                Forkop::Lt => o == Ordering::Less,
+                Forkop::Lte => o != Ordering::Greater,
+                _ => o != Ordering::Less,
+            })
+        },
+
+        Forkop::Add => {left.add(right)?},
+        Forkop::Sub => {left.sub(right)?},
+        Forkop::Mul => {left.mul(right)?},
+        Forkop::Div => {left.div(right)?},
+        Forkop::Pow => {left.pow(right)?},
+        Forkop::Mod => {left.mod_(right)?},
+        Forkop::Union => {left.union(right)?},
+        Forkop::Intersect => {left.intersection(right)?},
+        Forkop::In => Value::Bool(right.contains(&left)?),
+    };
+
+    Ok(v)
+}
+
+/// collects the names bound by p into `bound`, false if v does not fit
+fn matches(p : &Pattern, v : &Value, bound : &mut Vec<(String, Value)>) -> bool {
+    match (p, v) {
+        (Pattern::Wildcard, _) => true,
+        (Pattern::Bind(name), _) => {
+            bound.push((name.clone(), v.clone()));
+            true
+        },
+        (Pattern::Literal(x), _) => x == v,
+        (Pattern::Range { from, to }, _) => v.is_comparable() && from <= v && v < to,
+        (Pattern::List { items, rest }, Value::List(l)) => {
+            if l.len() < items.len() || (rest.is_none() && l.len() != items.len()) {
+                return false;
+            }
+
+            for (p, x) in items.iter().zip(l) {
+                if !matches(p, x, bound) {
+                    return false;
+                }
+            }
+
+            if let Some(name) = rest {
+                if name != "_" {
+                    bound.push((name.clone(), Value::List(l[items.len()..].to_vec())));
+                }
+            }
+
+            true
+        },
+        (Pattern::List { .. }, _) => false,
+        (Pattern::Struct { name, fields }, Value::Struct(s)) => {
+            if &s.name != name {
+                return false;
+            }
+
+            for (field, p) in fields {
+                match s.fields.iter().find(|(k, _)| k == field) {
+                    Some((_, x)) if matches(p, x, bound) => {},
+                    _ => return false,
+                }
+            }
+
+            true
+        },
+        (Pattern::Struct { .. }, _) => false,
+    }
+}
+
+/// one field or index of an assignment target
+enum Step {
+    Field(String),
+    /// evaluated once, before anything is assigned
+    Index(Value),
+}
+
+/// the variable `target` assigns to, the fields and indexes into it are pushed on `steps` outermost first
+fn place<'a>(env : &mut Environment, target : &'a Ast, steps : &mut Vec<Step>) -> Result<&'a String, RuntimeError> {
+    match target {
+        Ast::Get(name, _) => Ok(name),
+        Ast::Field { target, field } => {
+            let name = place(env, target, steps)?;
+            steps.push(Step::Field(field.clone()));
+            Ok(name)
+        },
+        Ast::Index { target, index } => {
+            let name = place(env, target, steps)?;
+            steps.push(Step::Index(index.eval(env)?));
+            Ok(name)
+        },
+        _ => Err(RuntimeError::new(ErrorKind::Type, format!("only variables, fields and indexes can be assigned"))),
+    }
+}
+
+/// the value at the end of `steps`
+fn load(v : Value, steps : &[Step]) -> Result<Value, RuntimeError> {
+    let Some((first, rest)) = steps.split_first() else {
+        return Ok(v);
+    };
+
+    let inner = match first {
+        Step::Field(f) => v.field(f)?,
+        Step::Index(i) => v.index(i)?,
+    };
+    load(inner, rest)
+}
+
+/// `v` with the value at the end of `steps` replaced by `new`
+fn replace(mut v : Value, steps : &[Step], new : Value) -> Result<Value, RuntimeError> {
+    let Some((first, rest)) = steps.split_first() else {
+        return Ok(new);
+    };
+
+    match first {
+        Step::Field(f) => {
+            let inner = replace(v.clone().field(f)?, rest, new)?;
+            v.set_field(f, inner)?;
+        },
+        Step::Index(i) => {
+            let inner = replace(v.index(i)?, rest, new)?;
+            v.set_index(i, inner)?;
+        },
+    }
+
+    Ok(v)
+}
+
+/// assigns `new` to the variable `name` or to the place `steps` lead to in it
+fn store(env : &mut Environment, name : &String, steps : &[Step], new : Value) -> Result<(), RuntimeError> {
+    if steps.is_empty() {
+        env.push_val(name, new);
+        return Ok(());
+    }
+
+    let Some(root) = env.fetch(name) else {
+        return Err(RuntimeError::new(ErrorKind::Name, format!("Variable: {} Not Found", name)));
+    };
+    let root = replace(root, steps, new)?;
+    env.push_val(name, root);
+    Ok(())
+}
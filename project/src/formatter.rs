@@ -0,0 +1,303 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use santiago::lexer::Lexeme;
+
+use super::parser::Syntax;
+use super::printer;
+
+/// lexemes the printer adds or drops, they are skipped when matching the source to the output
+const UNSTABLE : [&str; 4] = ["(", ")", "{", "}"];
+const CLOSING : [&str; 3] = [")", "}", "]"];
+
+/// how far ahead the source and the output are searched when their lexemes stop matching
+const RESYNC : usize = 8;
+
+/// a comment of the source, the lexer skips them so they are found between lexemes
+struct Comment {
+    text : String,
+    /// there is code before it on its line
+    trailing : bool,
+    /// an empty line separates it from what comes before
+    blank_before : bool,
+    /// the lexemes around it
+    prev : Option<usize>,
+    next : Option<usize>,
+}
+
+/// where a comment goes in the output, by line
+enum Place {
+    Above(usize),
+    End(usize),
+    Below(usize),
+    Last,
+}
+
+/// `text` printed with consistent layout, its comments kept next to the code they were next to. \
+/// empty lines between expressions are kept, several in a row become one
+pub fn format(syntax : &Syntax, text : &str) -> Result<String, String> {
+    let Ok(source) = santiago::lexer::lex(&syntax.lexer, text) else {
+        return Err(format!("Invalid Syntax!"));
+    };
+
+    let printed = printer::program(&syntax.parse(text)?);
+    let Ok(output) = santiago::lexer::lex(&syntax.lexer, &printed) else {
+        return Err(format!("the printer wrote code it can not read back"));
+    };
+
+    let (comments, blank_before) = comments(text, &source);
+    let matched = align(&source, &output);
+
+    let lines : Vec<&str> = printed.lines().collect();
+    let mut above : HashMap<usize, Vec<&Comment>> = HashMap::new();
+    let mut end : HashMap<usize, Vec<&Comment>> = HashMap::new();
+    let mut below : HashMap<usize, Vec<&Comment>> = HashMap::new();
+    let mut last = Vec::new();
+
+    for c in comments.iter() {
+        match place(c, &source, &output, &matched) {
+            Place::Above(l) => above.entry(l).or_default().push(c),
+            Place::End(l) => end.entry(l).or_default().push(c),
+            Place::Below(l) => below.entry(l).or_default().push(c),
+            Place::Last => last.push(c),
+        }
+    }
+
+    // empty lines in front of code that still starts a line
+    let mut empty = vec![false; lines.len()];
+    for (i, j) in matched.iter() {
+        let (i, j) = (*i, *j);
+        let first_in_source = i == 0 || end_line(&source[i - 1]) < source[i].position.line;
+        let first_in_output = j == 0 || end_line(&output[j - 1]) < output[j].position.line;
+        if blank_before[i] && first_in_source && first_in_output {
+            empty[output[j].position.line - 1] = true;
+        }
+    }
+
+    let mut out : Vec<String> = Vec::new();
+    let push_comment = |out : &mut Vec<String>, c : &Comment, indent : &str| {
+        if c.blank_before {
+            push_empty(out);
+        }
+        out.push(format!("{}{}", indent, c.text));
+    };
+
+    for (n, line) in lines.iter().enumerate() {
+        let l = n + 1;
+        let indent = &line[..line.len() - line.trim_start().len()];
+
+        for c in above.get(&l).into_iter().flatten() {
+            push_comment(&mut out, c, indent);
+        }
+        if empty[n] {
+            push_empty(&mut out);
+        }
+
+        let mut text = String::from(*line);
+        for c in end.get(&l).into_iter().flatten() {
+            text.push(' ');
+            text.push_str(&c.text);
+        }
+        out.push(text);
+
+        for c in below.get(&l).into_iter().flatten() {
+            push_comment(&mut out, c, indent);
+        }
+    }
+
+    for c in last {
+        push_comment(&mut out, c, "");
+    }
+
+    let mut ret = out.join("\n");
+    ret.push('\n');
+    Ok(ret)
+}
+
+/// whether formatting `text` leaves it as it is
+pub fn is_formatted(syntax : &Syntax, text : &str) -> Result<bool, String> {
+    Ok(format(syntax, text)? == text)
+}
+
+fn push_empty(out : &mut Vec<String>) -> () {
+    if out.last().map(|l| !l.is_empty()).unwrap_or(false) {
+        out.push(String::new());
+    }
+}
+
+/// the comments between the lexemes, and for every lexeme whether an empty line precedes it
+fn comments(text : &str, lexemes : &[Rc<Lexeme>]) -> (Vec<Comment>, Vec<bool>) {
+    let starts : Vec<usize> = std::iter::once(0).chain(text.match_indices('\n').map(|(i, _)| i + 1)).collect();
+    let offset = |l : &Lexeme| {
+        let line = starts[l.position.line - 1];
+        line + text[line..].char_indices().nth(l.position.column - 1).map(|(i, _)| i).unwrap_or(text.len() - line)
+    };
+
+    let mut comments = Vec::new();
+    let mut blank_before = Vec::with_capacity(lexemes.len());
+    let mut from = 0;
+
+    for i in 0..=lexemes.len() {
+        let to = if i < lexemes.len() { offset(&lexemes[i]) } else { text.len() };
+        let mut gap = &text[from.min(to)..to];
+        // newlines since the code or comment before, and since the lexeme before
+        let mut newlines = 0;
+        let mut since_code = 0;
+
+        loop {
+            let trimmed = gap.trim_start();
+            let skipped = gap[..gap.len() - trimmed.len()].matches('\n').count();
+            newlines += skipped;
+            since_code += skipped;
+            gap = trimmed;
+
+            let len = if gap.starts_with("//") {
+                gap.find('\n').unwrap_or(gap.len())
+            } else if gap.starts_with("/*") {
+                gap.find("*/").map(|e| e + 2).unwrap_or(gap.len())
+            } else {
+                break;
+            };
+
+            comments.push(Comment {
+                text : String::from(&gap[..len]),
+                trailing : i > 0 && since_code == 0,
+                blank_before : newlines >= 2,
+                prev : i.checked_sub(1),
+                next : if i < lexemes.len() { Some(i) } else { None },
+            });
+
+            newlines = 0;
+            since_code += gap[..len].matches('\n').count();
+            gap = &gap[len..];
+        }
+
+        if i < lexemes.len() {
+            blank_before.push(newlines >= 2);
+            from = to + lexemes[i].raw.len();
+        }
+    }
+
+    (comments, blank_before)
+}
+
+fn end_line(l : &Lexeme) -> usize {
+    l.position.line + l.raw.matches('\n').count()
+}
+
+/// what has to be equal for a lexeme of the source and one of the output to be the same. \
+/// literals may be written differently, `.5` is printed as `0.5`
+fn key(l : &Lexeme) -> Option<(&str, &str)> {
+    match l.kind.as_str() {
+        k if UNSTABLE.contains(&k) => None,
+        "NAME" => Some(("NAME", l.raw.as_str())),
+        "INTEGER" | "FLOAT" | "STRING" | "RAW_STRING" => Some(("literal", "")),
+        k => Some((k, "")),
+    }
+}
+
+/// the index in the output of every lexeme of the source that is also there
+fn align(source : &[Rc<Lexeme>], output : &[Rc<Lexeme>]) -> HashMap<usize, usize> {
+    let a : Vec<usize> = (0..source.len()).filter(|i| key(&source[*i]).is_some()).collect();
+    let b : Vec<usize> = (0..output.len()).filter(|j| key(&output[*j]).is_some()).collect();
+    let same = |i : usize, j : usize| i < a.len() && j < b.len() && key(&source[a[i]]) == key(&output[b[j]]);
+
+    let mut pairs = HashMap::new();
+    let (mut i, mut j) = (0, 0);
+
+    while i < a.len() && j < b.len() {
+        if same(i, j) {
+            pairs.insert(a[i], b[j]);
+            i += 1;
+            j += 1;
+            continue;
+        }
+
+        // the closest pair of positions where they agree again
+        let resync = (1..=RESYNC).flat_map(|d| (0..=d).map(move |di| (di, d - di))).find(|(di, dj)| same(i + di, j + dj));
+        match resync {
+            Some((di, dj)) => {
+                i += di;
+                j += dj;
+            },
+            None => break,
+        }
+    }
+
+    pairs
+}
+
+fn place(c : &Comment, source : &[Rc<Lexeme>], output : &[Rc<Lexeme>], matched : &HashMap<usize, usize>) -> Place {
+    let found = |i : usize| matched.get(&i).map(|o| &output[*o]);
+    // the closest lexeme at or before i that made it into the output
+    let before = |i : Option<usize>| (0..=i?).rev().find_map(found);
+
+    if c.trailing {
+        return match before(c.prev) {
+            Some(l) => Place::End(end_line(l)),
+            None => Place::Last,
+        };
+    }
+
+    let mut next = c.next;
+    while let Some(i) = next {
+        let kind = source[i].kind.as_str();
+        if CLOSING.contains(&kind) {
+            // the last thing inside brackets, it stays after what it followed
+            return match before(c.prev) {
+                Some(l) => Place::Below(end_line(l)),
+                None => Place::Last,
+            };
+        }
+        if let Some(l) = found(i) {
+            return Place::Above(l.position.line);
+        }
+        next = if i + 1 < source.len() { Some(i + 1) } else { None };
+    }
+
+    Place::Last
+}
+
+#[test]
+fn test_format() -> () {
+    let syntax = Syntax::new();
+    let text = "// fibonacci numbers
+fn fib(x) {x<=2:x,  // the first two
+fib(x-1)+fib(x-2)};
+
+
+/* a block
+   comment */
+x=fib(8) ;   /* trailing */ // twice
+while x>0 : [
+    // count down
+    x -= 1;
+    displn(x);
+    // done
+];
+// the end
+";
+
+    let formatted = format(&syntax, text).unwrap();
+    assert_eq!(formatted, "// fibonacci numbers
+fn fib(x) {
+    x <= 2 : x, // the first two
+    fib(x - 1) + fib(x - 2)
+};
+
+/* a block
+   comment */
+x = fib(8); /* trailing */ // twice
+while x > 0 : [
+    // count down
+    x -= 1;
+    displn(x);
+    // done
+];
+// the end
+");
+
+    assert!(is_formatted(&syntax, &formatted).unwrap());
+    assert!(!is_formatted(&syntax, text).unwrap());
+    assert!(format(&syntax, "x = ;").is_err());
+}
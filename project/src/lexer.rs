@@ -4,12 +4,20 @@ use santiago::lexer::LexerRules;
 pub fn rules() -> LexerRules {
     santiago::lexer_rules!(
         "DEFAULT" | "STRING"            = pattern r#"\"[^"]*\""#;
+        "DEFAULT" | "RAW_STRING"        = pattern r##"r"[^"]*""##;
+        "DEFAULT" | "RAW_STRING"        = pattern r###"r#"([^"]|"+[^"#])*"+#"###;
         "DEFAULT" | ""                  = pattern r#"//[^\n]*"#         =>  |lexer| {lexer.skip()};
-        "DEFAULT" | ""                  = pattern r#"/\*(.|\s)*\*/"#    =>  |lexer| {lexer.skip()};
+        "DEFAULT" | ""                  = pattern r#"/\*(.|\s)*?\*/"#    =>  |lexer| {lexer.skip()};
         "DEFAULT" | "INTEGER"           = pattern r#"\d+"#;
         "DEFAULT" | "FLOAT"             = pattern r#"\d+\.\d+"#;
         "DEFAULT" | "FLOAT"             = pattern r#"\.\d+"#;
         "DEFAULT" | "="                 = string  r#"="#;
+        "DEFAULT" | "+="                = string  r#"+="#;
+        "DEFAULT" | "-="                = string  r#"-="#;
+        "DEFAULT" | "*="                = string  r#"*="#;
+        "DEFAULT" | "/="                = string  r#"/="#;
+        "DEFAULT" | "%="                = string  r#"%="#;
+        "DEFAULT" | "^="                = string  r#"^="#;
         "DEFAULT" | "+"                 = string  r#"+"#;
         "DEFAULT" | "-"                 = string  r#"-"#;
         "DEFAULT" | "*"                 = string  r#"*"#;
@@ -23,6 +31,8 @@ pub fn rules() -> LexerRules {
         "DEFAULT" | "=="                = string  r#"=="#;
         "DEFAULT" | "!="                = string  r#"!="#;
         "DEFAULT" | "<="                = string  r#"<="#;
+        "DEFAULT" | "|"                 = string  r#"|"#;
+        "DEFAULT" | "&"                 = string  r#"&"#;
         "DEFAULT" | ":"                 = string  r#":"#;
         "DEFAULT" | ","                 = string  r#","#;
         "DEFAULT" | "("                 = string  r#"("#;
@@ -35,11 +45,27 @@ pub fn rules() -> LexerRules {
         "DEFAULT" | "$"                 = string  r#"$"#;
         "DEFAULT" | ";"                 = string  r#";"#;
         "DEFAULT" | "WHILE"             = string  r#"while"#;
+        "DEFAULT" | "TRY"               = string  r#"try"#;
+        "DEFAULT" | "CATCH"             = string  r#"catch"#;
+        "DEFAULT" | "THROW"             = string  r#"throw"#;
+        "DEFAULT" | "IMPORT"            = string  r#"import"#;
+        "DEFAULT" | "AS"                = string  r#"as"#;
+        "DEFAULT" | "IN"                = string  r#"in"#;
+        "DEFAULT" | "STRUCT"            = string  r#"struct"#;
+        "DEFAULT" | "MATCH"             = string  r#"match"#;
+        "DEFAULT" | "IF"                = string  r#"if"#;
+        "DEFAULT" | "=>"                = string  r#"=>"#;
+        "DEFAULT" | ".."                = string  r#".."#;
+        "DEFAULT" | "."                 = string  r#"."#;
+        "DEFAULT" | "..."               = string  r#"..."#;
         "DEFAULT" | "NAME"              = pattern r#"[a-zA-Z_][a-zA-Z0-9_]*"#;
         "DEFAULT" | ""                  = pattern r#"\s"#              =>  |lexer| {lexer.skip()};
     )
 }
 
+/// the words with a meaning of their own, offered by completion
+pub const KEYWORDS : [&str; 12] = ["fn", "while", "try", "catch", "throw", "import", "as", "struct", "match", "if", "in", "nil"];
+
 #[test]
 pub fn test() -> () {
     let text = 
@@ -66,7 +92,7 @@ while [1] {
 
     let rules = rules();
 
-    let tokens = santiago::lexer::lex(&rules, &text).unwrap();
+    let tokens = santiago::lexer::lex(&rules, text).unwrap();
 
     println!("{:#?}", tokens);
 }
\ No newline at end of file
@@ -0,0 +1,32 @@
+// the interpreter is written in a fairly explicit style (early returns, format! everywhere)
+#![allow(
+    clippy::needless_return,
+    clippy::useless_format,
+    clippy::unused_unit,
+)]
+
+pub mod lexer;
+pub mod parser;
+pub mod ast;
+pub mod value;
+pub mod eval;
+pub mod environment;
+pub mod error;
+pub mod run;
+pub mod output;
+pub mod limits;
+pub mod module;
+pub mod builtins;
+pub mod printer;
+pub mod formatter;
+pub mod check;
+pub mod lsp;
+pub mod debug;
+pub mod dap;
+pub mod profile;
+
+pub use run::Runtime;
+pub use value::{Value, Struct, ConversionError};
+pub use environment::{Environment, NativeFunction};
+pub use error::{RuntimeError, ErrorKind};
+pub use limits::Limits;
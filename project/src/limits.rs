@@ -0,0 +1,50 @@
+use std::time::Duration;
+
+/// resource limits for a single `Runtime::run`. \
+/// None means unlimited, except for the call depth which always has a bound
+/// since every nested call keeps its frames alive until it returns.
+#[derive(Debug, Clone)]
+pub struct Limits {
+    pub max_steps : Option<u64>,
+    pub max_depth : usize,
+    pub max_size : Option<usize>,
+    pub timeout : Option<Duration>,
+}
+
+impl Limits {
+    pub const DEFAULT_DEPTH : usize = 10_000;
+
+    pub fn new() -> Limits {
+        Limits { max_steps : None, max_depth : Limits::DEFAULT_DEPTH, max_size : None, timeout : None }
+    }
+
+    /// maximum number of evaluated expressions
+    pub fn steps(mut self, n : u64) -> Limits {
+        self.max_steps = Some(n);
+        self
+    }
+
+    /// maximum number of nested script function calls
+    pub fn depth(mut self, n : usize) -> Limits {
+        self.max_depth = n;
+        self
+    }
+
+    /// maximum number of elements in a list or bytes in a string
+    pub fn size(mut self, n : usize) -> Limits {
+        self.max_size = Some(n);
+        self
+    }
+
+    /// wall clock time a run may take
+    pub fn timeout(mut self, d : Duration) -> Limits {
+        self.timeout = Some(d);
+        self
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Limits {
+        Limits::new()
+    }
+}
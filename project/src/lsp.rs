@@ -0,0 +1,471 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::notification::{DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, Notification as _, PublishDiagnostics};
+use lsp_types::request::{Completion, DocumentSymbolRequest, Formatting, GotoDefinition, HoverRequest, Request as _};
+use lsp_types::*;
+
+use super::ast::{Ast, Span, Vast};
+use super::builtins;
+use super::check::{self, walk, walk_function, Lints, Severity};
+use super::environment::{Environment, BUILTINS};
+use super::formatter;
+use super::lexer::KEYWORDS;
+use super::printer;
+
+/// an open file, the tree is the one of the last version that parsed
+struct Document {
+    text : String,
+    tree : Option<Vast>,
+}
+
+/// answers the requests of an editor about the files it has open
+pub struct Server {
+    env : Environment,
+    documents : HashMap<Url, Document>,
+}
+
+/// what the server can do, sent back on initialize
+pub fn capabilities() -> ServerCapabilities {
+    ServerCapabilities {
+        text_document_sync : Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        definition_provider : Some(OneOf::Left(true)),
+        hover_provider : Some(HoverProviderCapability::Simple(true)),
+        completion_provider : Some(CompletionOptions::default()),
+        document_symbol_provider : Some(OneOf::Left(true)),
+        document_formatting_provider : Some(OneOf::Left(true)),
+        ..ServerCapabilities::default()
+    }
+}
+
+/// runs the protocol on `connection` until the editor shuts the server down
+pub fn serve(connection : &Connection) -> Result<(), Box<dyn Error + Sync + Send>> {
+    connection.initialize(serde_json::to_value(capabilities())?)?;
+    let mut server = Server::new();
+
+    for message in &connection.receiver {
+        match message {
+            Message::Request(req) => {
+                if connection.handle_shutdown(&req)? {
+                    return Ok(());
+                }
+                connection.sender.send(Message::Response(server.request(req)))?;
+            },
+            Message::Notification(n) => {
+                for out in server.notify(n) {
+                    connection.sender.send(Message::Notification(out))?;
+                }
+            },
+            Message::Response(_) => {},
+        }
+    }
+
+    Ok(())
+}
+
+/// decodes the parameters of `req` as those of R and encodes what f answers
+fn handle<R : lsp_types::request::Request>(req : Request, f : impl FnOnce(R::Params) -> R::Result) -> Response {
+    match serde_json::from_value::<R::Params>(req.params) {
+        Ok(params) => Response::new_ok(req.id, f(params)),
+        Err(e) => Response::new_err(req.id, ErrorCode::InvalidParams as i32, format!("{}", e)),
+    }
+}
+
+impl Default for Server {
+    fn default() -> Server {
+        Server::new()
+    }
+}
+
+impl Server {
+    pub fn new() -> Server {
+        Server { env : Environment::new(), documents : HashMap::new() }
+    }
+
+    pub fn request(&mut self, req : Request) -> Response {
+        match req.method.as_str() {
+            GotoDefinition::METHOD => handle::<GotoDefinition>(req, |p| {
+                let at = p.text_document_position_params;
+                let text = &self.documents.get(&at.text_document.uri)?.text;
+                let range = self.definition(text, at.position)?;
+                Some(GotoDefinitionResponse::Scalar(Location { uri : at.text_document.uri, range }))
+            }),
+            HoverRequest::METHOD => handle::<HoverRequest>(req, |p| {
+                let at = p.text_document_position_params;
+                let text = &self.documents.get(&at.text_document.uri)?.text;
+                let value = self.hover(text, at.position)?;
+                Some(Hover { contents : HoverContents::Markup(MarkupContent { kind : MarkupKind::Markdown, value }), range : None })
+            }),
+            Completion::METHOD => handle::<Completion>(req, |p| {
+                let d = self.documents.get(&p.text_document_position.text_document.uri);
+                Some(CompletionResponse::Array(completion(d.and_then(|d| d.tree.as_ref()))))
+            }),
+            DocumentSymbolRequest::METHOD => handle::<DocumentSymbolRequest>(req, |p| {
+                let text = &self.documents.get(&p.text_document.uri)?.text;
+                Some(DocumentSymbolResponse::Nested(self.symbols(text)))
+            }),
+            Formatting::METHOD => handle::<Formatting>(req, |p| {
+                let text = &self.documents.get(&p.text_document.uri)?.text;
+                self.formatting(text)
+            }),
+            m => Response::new_err(req.id, ErrorCode::MethodNotFound as i32, format!("{} is not supported", m)),
+        }
+    }
+
+    /// keeps the open files up to date, the diagnostics of a file are sent back whenever it changes
+    pub fn notify(&mut self, n : Notification) -> Vec<Notification> {
+        let uri = match n.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let Ok(p) = serde_json::from_value::<DidOpenTextDocumentParams>(n.params) else {
+                    return vec![];
+                };
+                self.documents.insert(p.text_document.uri.clone(), Document { text : String::new(), tree : None });
+                self.update(&p.text_document.uri, p.text_document.text);
+                p.text_document.uri
+            },
+            // the server asks for whole documents, so the last change is the text
+            DidChangeTextDocument::METHOD => {
+                let Ok(p) = serde_json::from_value::<DidChangeTextDocumentParams>(n.params) else {
+                    return vec![];
+                };
+                let Some(change) = p.content_changes.into_iter().last() else {
+                    return vec![];
+                };
+                self.update(&p.text_document.uri, change.text);
+                p.text_document.uri
+            },
+            DidCloseTextDocument::METHOD => {
+                let Ok(p) = serde_json::from_value::<DidCloseTextDocumentParams>(n.params) else {
+                    return vec![];
+                };
+                self.documents.remove(&p.text_document.uri);
+
+                let params = PublishDiagnosticsParams { uri : p.text_document.uri, diagnostics : vec![], version : None };
+                return vec![Notification::new(String::from(PublishDiagnostics::METHOD), params)];
+            },
+            _ => return vec![],
+        };
+
+        let Some(d) = self.documents.get(&uri) else {
+            return vec![];
+        };
+        let params = PublishDiagnosticsParams { diagnostics : self.diagnostics(&d.text), uri, version : None };
+        vec![Notification::new(String::from(PublishDiagnostics::METHOD), params)]
+    }
+
+    fn update(&mut self, uri : &Url, text : String) -> () {
+        let tree = self.env.syntax.parse(&text).ok();
+        if let Some(d) = self.documents.get_mut(uri) {
+            d.tree = tree.or(d.tree.take());
+            d.text = text;
+        }
+    }
+
+    /// the syntax error of `text`, or what the linter finds in it
+    pub fn diagnostics(&self, text : &str) -> Vec<Diagnostic> {
+        let problems = match check::lint(text, &self.env, &Lints::new()) {
+            Ok(p) => p,
+            Err(message) => {
+                let at = self.env.syntax.error_at(text).unwrap_or(Span { line : 1, column : 1 });
+                return vec![Diagnostic {
+                    range : self.range(text, at),
+                    severity : Some(DiagnosticSeverity::ERROR),
+                    source : Some(String::from("check")),
+                    message,
+                    ..Diagnostic::default()
+                }];
+            },
+        };
+
+        problems.into_iter().map(|p| Diagnostic {
+            range : self.range(text, p.span.unwrap_or(Span { line : 1, column : 1 })),
+            severity : Some(if p.severity == Severity::Error { DiagnosticSeverity::ERROR } else { DiagnosticSeverity::WARNING }),
+            code : Some(NumberOrString::String(String::from(p.lint))),
+            source : Some(String::from("check")),
+            message : p.message,
+            ..Diagnostic::default()
+        }).collect()
+    }
+
+    /// where the function, parameter or variable named at `at` is defined
+    pub fn definition(&self, text : &str, at : Position) -> Option<Range> {
+        let tree = self.env.syntax.parse(text).ok()?;
+        let (name, span) = self.name_at(text, at)?;
+
+        let function = tree.iter().find_map(|a| enclosing(a, span, None)).flatten();
+        let call = tree.iter().any(|a| is_call(a, span));
+
+        let local = || match function {
+            Some(Ast::FunDef { args, body, .. }) => args.iter().find(|p| p.name == name).map(|p| p.span).or_else(|| assignment(body, &name)),
+            _ => None,
+        };
+        let global = || tree.iter().find_map(|a| assignment(a, &name));
+        let defined = || definitions(&tree).into_iter().find(|a| defined_name(a) == Some(&name)).and_then(|a| match a {
+            Ast::FunDef { span, .. } | Ast::StructDef { span, .. } => Some(*span),
+            _ => None,
+        });
+
+        // a call finds a function before a variable holding one
+        let span = if call { defined().or_else(local).or_else(global) } else { local().or_else(global).or_else(defined) };
+        Some(self.range(text, span?))
+    }
+
+    /// the signature of a function or struct of the file, or the documentation of a builtin
+    pub fn hover(&self, text : &str, at : Position) -> Option<String> {
+        let (name, _) = self.name_at(text, at)?;
+
+        let tree = self.env.syntax.parse(text).unwrap_or_default();
+        if let Some(a) = definitions(&tree).into_iter().find(|a| defined_name(a) == Some(&name)) {
+            return Some(format!("```\n{}\n```", printer::signature(a)?));
+        }
+
+        builtins::doc(&name).map(|d| match d.split_once(") ") {
+            Some((signature, description)) => format!("```\n{})\n```\n{}", signature, description),
+            None => format!("```\n{}\n```", d),
+        })
+    }
+
+    /// the functions and structs of the file, and its global variables
+    pub fn symbols(&self, text : &str) -> Vec<DocumentSymbol> {
+        let Ok(tree) = self.env.syntax.parse(text) else {
+            return vec![];
+        };
+
+        let mut found : Vec<(String, Span, SymbolKind, Option<String>)> = definitions(&tree).into_iter().filter_map(|a| match a {
+            Ast::FunDef { name, span, .. } => Some((name.clone(), *span, SymbolKind::FUNCTION, printer::signature(a))),
+            Ast::StructDef { name, span, .. } => Some((name.clone(), *span, SymbolKind::STRUCT, printer::signature(a))),
+            _ => None,
+        }).collect();
+
+        let mut seen = HashSet::new();
+        for a in tree.iter() {
+            walk_function(a, &mut |a| {
+                if let Ast::Set { name, span, .. } = a {
+                    if seen.insert(name) {
+                        found.push((name.clone(), *span, SymbolKind::VARIABLE, None));
+                    }
+                }
+            });
+        }
+        found.sort_by_key(|(_, span, _, _)| *span);
+
+        #[allow(deprecated)]
+        found.into_iter().map(|(name, span, kind, detail)| DocumentSymbol {
+            range : self.range(text, span),
+            selection_range : self.range(text, span),
+            name,
+            detail,
+            kind,
+            tags : None,
+            deprecated : None,
+            children : None,
+        }).collect()
+    }
+
+    /// one edit replacing the whole text when it is not formatted, None when it does not parse
+    pub fn formatting(&self, text : &str) -> Option<Vec<TextEdit>> {
+        let formatted = formatter::format(&self.env.syntax, text).ok()?;
+        if formatted == text {
+            return Some(vec![]);
+        }
+
+        let last = text.rsplit('\n').next().unwrap_or("");
+        let end = Position::new(text.split('\n').count() as u32 - 1, utf16(last));
+        Some(vec![TextEdit { range : Range::new(Position::new(0, 0), end), new_text : formatted }])
+    }
+
+    /// the name under `at` or right before it, with the span of its first character
+    fn name_at(&self, text : &str, at : Position) -> Option<(String, Span)> {
+        let lexemes = santiago::lexer::lex(&self.env.syntax.lexer, text).ok()?;
+        let at = span(text, at);
+
+        lexemes.iter().find(|l| {
+            l.kind == "NAME" && l.position.line == at.line && l.position.column <= at.column && at.column <= l.position.column + l.raw.chars().count()
+        }).map(|l| (l.raw.clone(), Span { line : l.position.line, column : l.position.column }))
+    }
+
+    /// the lexeme starting at `at`, or its first character when there is none
+    fn range(&self, text : &str, at : Span) -> Range {
+        let length = santiago::lexer::lex(&self.env.syntax.lexer, text).ok()
+            .and_then(|lexemes| lexemes.iter().find(|l| l.position.line == at.line && l.position.column == at.column).map(|l| l.raw.chars().count()))
+            .unwrap_or(1);
+
+        Range::new(position(text, at), position(text, Span { line : at.line, column : at.column + length }))
+    }
+}
+
+/// keywords, builtins and every name the file defines
+fn completion(tree : Option<&Vast>) -> Vec<CompletionItem> {
+    let item = |label : &str, kind, detail : Option<String>| CompletionItem { label : String::from(label), kind : Some(kind), detail, ..CompletionItem::default() };
+
+    let mut items : Vec<CompletionItem> = KEYWORDS.iter().map(|k| item(k, CompletionItemKind::KEYWORD, None)).collect();
+    items.extend(BUILTINS.iter().copied().chain(builtins::names()).map(|b| item(b, CompletionItemKind::FUNCTION, builtins::doc(b).map(String::from))));
+
+    for a in tree.into_iter().flatten() {
+        walk(a, &mut |a| match a {
+            Ast::FunDef { name, args, .. } => {
+                items.push(item(name, CompletionItemKind::FUNCTION, printer::signature(a)));
+                items.extend(args.iter().map(|p| item(&p.name, CompletionItemKind::VARIABLE, None)));
+            },
+            Ast::StructDef { name, .. } => items.push(item(name, CompletionItemKind::STRUCT, printer::signature(a))),
+            Ast::Set { name, .. } => items.push(item(name, CompletionItemKind::VARIABLE, None)),
+            _ => {},
+        });
+    }
+
+    let mut seen = HashSet::new();
+    items.retain(|i| seen.insert(i.label.clone()));
+    items
+}
+
+/// every function and struct definition, nested ones included
+fn definitions(tree : &Vast) -> Vec<&Ast> {
+    let mut found = Vec::new();
+    for a in tree {
+        walk(a, &mut |a| {
+            if let Ast::FunDef { .. } | Ast::StructDef { .. } = a {
+                found.push(a);
+            }
+        });
+    }
+    found
+}
+
+fn defined_name(a : &Ast) -> Option<&String> {
+    match a {
+        Ast::FunDef { name, .. } | Ast::StructDef { name, .. } => Some(name),
+        _ => None,
+    }
+}
+
+/// Some(the function around the node recorded at `at`) when `a` holds that node
+fn enclosing<'a>(a : &'a Ast, at : Span, function : Option<&'a Ast>) -> Option<Option<&'a Ast>> {
+    match a {
+        Ast::Get(_, s) | Ast::Call { span : s, .. } | Ast::Set { span : s, .. } | Ast::FunDef { span : s, .. } | Ast::StructDef { span : s, .. } if *s == at => return Some(function),
+        Ast::FunDef { args, .. } if args.iter().any(|p| p.span == at) => return Some(Some(a)),
+        _ => {},
+    }
+
+    let function = if let Ast::FunDef { .. } = a { Some(a) } else { function };
+    a.children().into_iter().find_map(|c| enclosing(c, at, function))
+}
+
+fn is_call(a : &Ast, at : Span) -> bool {
+    let mut found = false;
+    walk(a, &mut |a| {
+        if let Ast::Call { span, .. } = a {
+            found |= *span == at;
+        }
+    });
+    found
+}
+
+/// the first assignment to `name` in `a`, outside of the functions it defines
+fn assignment(a : &Ast, name : &String) -> Option<Span> {
+    let mut found = None;
+    walk_function(a, &mut |a| {
+        if let Ast::Set { name : n, span, .. } = a {
+            if n == name && found.is_none() {
+                found = Some(*span);
+            }
+        }
+    });
+    found
+}
+
+fn utf16(s : &str) -> u32 {
+    s.chars().map(char::len_utf16).sum::<usize>() as u32
+}
+
+/// spans count lines and chars from 1, positions count lines from 0 and UTF-16 code units
+fn position(text : &str, at : Span) -> Position {
+    let line = text.split('\n').nth(at.line - 1).unwrap_or("");
+    let before : String = line.chars().take(at.column - 1).collect();
+    Position::new(at.line as u32 - 1, utf16(&before))
+}
+
+fn span(text : &str, at : Position) -> Span {
+    let line = text.split('\n').nth(at.line as usize).unwrap_or("");
+
+    let mut units = 0;
+    let mut column = 1;
+    for c in line.chars() {
+        if units >= at.character as usize {
+            break;
+        }
+        units += c.len_utf16();
+        column += 1;
+    }
+
+    Span { line : at.line as usize + 1, column }
+}
+
+#[test]
+fn test_lsp() -> () {
+    use lsp_server::RequestId;
+    use serde_json::{json, Value};
+
+    let (client, server) = Connection::memory();
+    let thread = std::thread::spawn(move || serve(&server).unwrap());
+
+    let mut id = 0;
+    let mut request = |method : &str, params : Value| -> Value {
+        id += 1;
+        client.sender.send(Message::Request(Request::new(RequestId::from(id), String::from(method), params))).unwrap();
+        match client.receiver.recv().unwrap() {
+            Message::Response(r) => r.result.unwrap_or(Value::Null),
+            m => panic!("expected a response, got {:?}", m),
+        }
+    };
+    let notify = |method : &str, params : Value| client.sender.send(Message::Notification(Notification::new(String::from(method), params))).unwrap();
+    let diagnostics = || match client.receiver.recv().unwrap() {
+        Message::Notification(n) => n.params["diagnostics"].as_array().unwrap().iter().map(|d| (d["range"]["start"].to_string(), d["message"].as_str().unwrap().to_string())).collect::<Vec<_>>(),
+        m => panic!("expected diagnostics, got {:?}", m),
+    };
+
+    let capabilities = request("initialize", json!({ "capabilities" : {} }));
+    assert_eq!(capabilities["capabilities"]["documentFormattingProvider"], json!(true));
+    notify("initialized", json!({}));
+
+    let uri = "file:///tmp/area.txt";
+    let text = "fn area(w, h = 1) { w * h };\nstruct Point { x, y };\nsize = area(2, 3);\ndispln(len(\"é\"), siz);\n";
+    notify("textDocument/didOpen", json!({ "textDocument" : { "uri" : uri, "languageId" : "script", "version" : 1, "text" : text } }));
+    assert_eq!(diagnostics(), vec![(json!({ "line" : 3, "character" : 17 }).to_string(), String::from("siz is never assigned"))]);
+
+    let at = |line : u32, character : u32| json!({ "textDocument" : { "uri" : uri }, "position" : { "line" : line, "character" : character } });
+
+    let definition = request("textDocument/definition", at(2, 9));
+    assert_eq!(definition["range"]["start"], json!({ "line" : 0, "character" : 3 }));
+    let parameter = request("textDocument/definition", at(0, 24));
+    assert_eq!(parameter["range"], json!({ "start" : { "line" : 0, "character" : 11 }, "end" : { "line" : 0, "character" : 12 } }));
+
+    assert_eq!(request("textDocument/hover", at(2, 8))["contents"]["value"], json!("```\nfn area(w, h = 1)\n```"));
+    assert_eq!(request("textDocument/hover", at(3, 8))["contents"]["value"], json!("```\nlen(v)\n```\nchars of a string or elements of a list or set"));
+    assert_eq!(request("textDocument/hover", at(1, 0)), Value::Null);
+
+    let completion = request("textDocument/completion", at(3, 0));
+    let labels : Vec<&str> = completion.as_array().unwrap().iter().map(|i| i["label"].as_str().unwrap()).collect();
+    for l in ["while", "displn", "re_match", "area", "Point", "size", "h"] {
+        assert!(labels.contains(&l), "{} is not completed", l);
+    }
+
+    let symbols = request("textDocument/documentSymbol", json!({ "textDocument" : { "uri" : uri } }));
+    let names : Vec<&str> = symbols.as_array().unwrap().iter().map(|s| s["name"].as_str().unwrap()).collect();
+    assert_eq!(names, vec!["area", "Point", "size"]);
+
+    // a syntax error is reported where it is, the last tree that parsed still completes
+    notify("textDocument/didChange", json!({ "textDocument" : { "uri" : uri, "version" : 2 }, "contentChanges" : [{ "text" : "x = 1;\ny = (x;\n" }] }));
+    assert_eq!(diagnostics(), vec![(json!({ "line" : 1, "character" : 6 }).to_string(), String::from("Invalid Structure of Program!"))]);
+    assert!(request("textDocument/completion", at(0, 0)).to_string().contains("\"area\""));
+    assert_eq!(request("textDocument/formatting", json!({ "textDocument" : { "uri" : uri }, "options" : { "tabSize" : 4, "insertSpaces" : true } })), Value::Null);
+
+    notify("textDocument/didChange", json!({ "textDocument" : { "uri" : uri, "version" : 3 }, "contentChanges" : [{ "text" : "x=1 ;" }] }));
+    assert_eq!(diagnostics(), vec![]);
+    let edits = request("textDocument/formatting", json!({ "textDocument" : { "uri" : uri }, "options" : { "tabSize" : 4, "insertSpaces" : true } }));
+    assert_eq!(edits, json!([{ "range" : { "start" : { "line" : 0, "character" : 0 }, "end" : { "line" : 0, "character" : 5 } }, "newText" : "x = 1;\n" }]));
+
+    request("shutdown", Value::Null);
+    notify("exit", Value::Null);
+    thread.join().unwrap();
+}
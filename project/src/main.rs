@@ -1,37 +1,289 @@
-mod lexer;
-mod parser;
-mod ast;
-mod value;
-mod eval;
-mod environment;
-mod error;
-mod run;
+#![allow(clippy::unused_unit)]
 
+mod repl;
+
+use std::collections::BTreeSet;
+use std::io::{Read, Write};
+use std::path::Path;
 use std::process::exit;
-use run::Runtime;
 
-fn main() -> () {
-    let argv : Vec<String> = std::env::args().collect();
+use project::parser::Syntax;
+use project::debug::{Console, Debugger};
+use project::profile::Profiler;
+use project::{check, dap, formatter, ErrorKind, Runtime};
+
+/// exit status of a script that raised an uncaught error, a check that found errors or an unformatted file
+const FAILED : i32 = 1;
+/// exit status for bad usage, unreadable files and syntax errors
+const INVALID : i32 = 2;
+/// evaluation steps between two samples of `profile`
+const SAMPLE_INTERVAL : u64 = 64;
+
+const USAGE : &str = "\
+usage:
+    {c}                          start the shell
+    {c} repl                     start the shell
+    {c} run FILE [ARGS...]       run a script, FILE may be - for standard input
+    {c} FILE [ARGS...]           same as run
+    {c} -e CODE [ARGS...]        run CODE
+    {c} debug FILE [ARGS...]     run a script in the step debugger, paused on its first line
+    {c} debug --dap              serve the Debug Adapter Protocol on standard input and output
+    {c} profile [--folded OUT] FILE [ARGS...]
+                                 run a script and print the time spent in each function,
+                                 --folded writes sampled call stacks for flamegraph tools
+    {c} check [OPTIONS] FILE     report mistakes that can be found without running,
+                                 --allow, --warn or --deny LINT change how a lint is reported
+    {c} fmt [--check] FILE       print the script formatted, --check fails if it is not
+    {c} tokens FILE              print the lexemes of the script
+    {c} ast FILE                 print the syntax tree of the script";
 
-    if argv.len() > 2 {
-        let c = argv[0].clone();
-        println!("Incorrect usage of utility {c}.\nCorrect Usage:\n\t{c} # for command line util\n\t{c} FILENAME # to run file");
-        exit(1);
+fn usage(program : &str) -> ! {
+    eprintln!("{}", USAGE.replace("{c}", program));
+    exit(INVALID);
+}
+
+/// the text of a script, `-` is standard input
+fn read(path : &str) -> String {
+    let bytes = if path == "-" {
+        let mut bytes = Vec::new();
+        std::io::stdin().read_to_end(&mut bytes).map(|_| bytes)
+    } else {
+        std::fs::read(path)
+    };
+
+    let bytes = match bytes {
+        Ok(b) => b,
+        Err(e) => {
+            eprintln!("could not read {} : {}", path, e);
+            exit(INVALID);
+        },
+    };
+
+    match String::from_utf8(bytes) {
+        Ok(text) => text,
+        Err(e) => {
+            eprintln!("{} is not valid UTF-8 (at byte {})", path, e.utf8_error().valid_up_to());
+            exit(INVALID);
+        },
     }
+}
 
+fn parse(r : &Runtime, path : &str, text : &str) -> project::ast::Vast {
+    match r.parse(text) {
+        Ok(tree) => tree,
+        Err(e) => {
+            eprintln!("{} : {}", path, e);
+            exit(INVALID);
+        },
+    }
+}
+
+/// how `run` watches the script
+enum Mode<'a> {
+    Plain,
+    Debug(Debugger),
+    /// reports on standard error, and writes the folded stacks to the file if there is one
+    Profile(Option<&'a str>),
+}
+
+/// runs `text`, imports are relative to `path`
+fn run(path : &str, text : &str, args : &[String], mode : Mode) -> () {
     let mut r = Runtime::new();
+    r.set_args(args.to_vec());
+    let folded = match mode {
+        Mode::Plain => None,
+        Mode::Debug(d) => {
+            r.set_debugger(d);
+            None
+        },
+        Mode::Profile(folded) => {
+            r.set_profiler(Profiler::new(SAMPLE_INTERVAL));
+            Some(folded)
+        },
+    };
+
+    let tree = parse(&r, path, text);
+
+    // standard input and -e code import relative to the working directory
+    let file = if path == "-" || path == "-e" { Path::new(".").join(path) } else { Path::new(path).to_path_buf() };
+    let file = file.canonicalize().unwrap_or(file);
+
+    r.environment().loading.push(file);
+    let ret = r.execute(&tree);
+    r.environment().loading.pop();
+
+    // exit skips the destructors that would flush it
+    let _ = r.environment().output.flush();
 
-    if argv.len() == 1 {
-        println!(r##"Shell Mode! Hello! 'Ctrl+C' to Exit"##);
-        
-        loop {
-            let mut input = String::new();
-            std::io::stdin().read_line(&mut input).unwrap();
-            println!("Result : {}", r.run(input.as_str()));
+    if let (Some(folded), Some(p)) = (folded, r.profiler()) {
+        eprint!("{}", p.summary());
+        if let Some(out) = folded {
+            if let Err(e) = std::fs::write(out, p.folded()) {
+                eprintln!("could not write {} : {}", out, e);
+                exit(INVALID);
+            }
         }
     }
-    else {
-        let f = (String::from_utf8(std::fs::read(argv[1].as_str()).unwrap())).unwrap();
-        r.run(f.as_str());
+
+    match ret {
+        Ok(_) => {},
+        Err(e) if e.kind == ErrorKind::Exit => exit(e.exit_code()),
+        Err(e) => {
+            eprintln!("{}", e);
+            exit(FAILED);
+        },
+    }
+}
+
+fn main() -> () {
+    let argv : Vec<String> = std::env::args().collect();
+    let program = argv[0].as_str();
+
+    let Some(command) = argv.get(1) else {
+        repl::start();
+        return;
+    };
+
+    // FILE for the commands that take exactly one
+    let file = || match &argv[2..] {
+        [f] => f.as_str(),
+        _ => usage(program),
+    };
+
+    match command.as_str() {
+        "repl" => match argv.len() {
+            2 => repl::start(),
+            _ => usage(program),
+        },
+        "run" => match argv.get(2) {
+            Some(f) => run(f, &read(f), &argv[3..], Mode::Plain),
+            None => usage(program),
+        },
+        "-e" => match argv.get(2) {
+            Some(code) => run("-e", code, &argv[3..], Mode::Plain),
+            None => usage(program),
+        },
+        "debug" => match argv.get(2).map(|a| a.as_str()) {
+            Some("--dap") if argv.len() == 3 => dap::serve(Box::new(std::io::stdin().lock()), Box::new(std::io::stdout())),
+            Some(f) if f != "-" && !f.starts_with('-') => {
+                let text = read(f);
+                println!("debugging {}, type help for the commands", f);
+
+                let console = Console::new(&text, Box::new(std::io::stdin().lock()), Box::new(std::io::stdout()));
+                run(f, &text, &argv[3..], Mode::Debug(Debugger::new(Box::new(console), BTreeSet::new(), true)));
+            },
+            _ => usage(program),
+        },
+        "profile" => {
+            let (folded, rest) = match argv.get(2).map(|a| a.as_str()) {
+                Some("--folded") => match argv.get(3) {
+                    Some(out) => (Some(out.as_str()), &argv[4..]),
+                    None => usage(program),
+                },
+                _ => (None, &argv[2..]),
+            };
+
+            match rest.split_first() {
+                Some((f, args)) if f == "-" || !f.starts_with('-') => run(f, &read(f), args, Mode::Profile(folded)),
+                _ => usage(program),
+            }
+        },
+        "check" => {
+            let mut lints = check::Lints::new();
+            let mut path = None;
+
+            let mut rest = argv[2..].iter();
+            while let Some(a) = rest.next() {
+                let severity = match a.as_str() {
+                    "--allow" => check::Severity::Allow,
+                    "--warn" => check::Severity::Warning,
+                    "--deny" => check::Severity::Error,
+                    f if path.is_none() && (f == "-" || !f.starts_with('-')) => {
+                        path = Some(f);
+                        continue;
+                    },
+                    _ => usage(program),
+                };
+                let Some(lint) = rest.next() else {
+                    usage(program);
+                };
+                if let Err(e) = lints.set(lint, severity) {
+                    eprintln!("{}", e);
+                    exit(INVALID);
+                }
+            }
+
+            let Some(path) = path else {
+                usage(program);
+            };
+            let text = read(path);
+            let mut r = Runtime::new();
+
+            let problems = match check::lint(&text, r.environment(), &lints) {
+                Ok(p) => p,
+                Err(e) => {
+                    eprintln!("{} : {}", path, e);
+                    exit(INVALID);
+                },
+            };
+
+            for p in problems.iter() {
+                eprintln!("{}:{}", path, p);
+            }
+            if problems.iter().any(|p| p.severity == check::Severity::Error) {
+                exit(FAILED);
+            }
+        },
+        "fmt" => {
+            let (check, path) = match &argv[2..] {
+                [f] => (false, f.as_str()),
+                [c, f] if c == "--check" => (true, f.as_str()),
+                _ => usage(program),
+            };
+            let text = read(path);
+
+            let formatted = match formatter::format(&Syntax::new(), &text) {
+                Ok(f) => f,
+                Err(e) => {
+                    eprintln!("{} : {}", path, e);
+                    exit(INVALID);
+                },
+            };
+
+            if !check {
+                print!("{}", formatted);
+            } else if formatted != text {
+                eprintln!("{} is not formatted", path);
+                exit(FAILED);
+            }
+        },
+        "tokens" => {
+            let path = file();
+            let text = read(path);
+            let syntax = Syntax::new();
+
+            match santiago::lexer::lex(&syntax.lexer, &text) {
+                Ok(lexemes) => {
+                    for l in lexemes {
+                        println!("{}:{}\t{}\t{}", l.position.line, l.position.column, l.kind, l.raw);
+                    }
+                },
+                Err(e) => {
+                    eprintln!("{} : {}", path, e);
+                    exit(INVALID);
+                },
+            }
+        },
+        "ast" => {
+            let path = file();
+            let text = read(path);
+
+            for a in parse(&Runtime::new(), path, &text) {
+                println!("{:#?}", a);
+            }
+        },
+        "help" | "-h" | "--help" => println!("{}", USAGE.replace("{c}", program)),
+        f if f == "-" || !f.starts_with('-') => run(f, &read(f), &argv[2..], Mode::Plain),
+        _ => usage(program),
     }
 }
@@ -0,0 +1,168 @@
+use std::path::{Path, PathBuf};
+
+use super::environment::Environment;
+use super::error::{ErrorKind, RuntimeError};
+use super::value::Value;
+
+/// extension tried when an import names a file without one
+pub const SCRIPT_EXTENSION : &str = "txt";
+
+impl Environment {
+    /// `import path as alias`. evaluates the file the first time it is imported,
+    /// later imports of the same file only bind the alias
+    pub fn import(&mut self, path : &String, alias : &Option<String>) -> Result<Value, RuntimeError> {
+        let Some(file) = self.resolve_import(path) else {
+            return Err(RuntimeError::new(ErrorKind::Import, format!("module {} not found", path)));
+        };
+
+        if let Some(i) = self.loading.iter().position(|f| *f == file) {
+            let mut cycle : Vec<String> = self.loading[i..].iter().map(|f| format!("{}", f.display())).collect();
+            cycle.push(format!("{}", file.display()));
+            return Err(RuntimeError::new(ErrorKind::Import, format!("import cycle: {}", cycle.join(" -> "))));
+        }
+
+        let module = match self.modules.get(&file) {
+            Some(m) => m.clone(),
+            None => self.load_module(&file)?,
+        };
+
+        let alias = match alias {
+            Some(a) => a.clone(),
+            None => module_name(&file),
+        };
+
+        self.aliases.insert((self.namespace.clone(), alias), module);
+
+        Ok(Value::Nil)
+    }
+
+    /// relative to the importing file (or the working directory), then the search paths
+    pub fn resolve_import(&self, path : &String) -> Option<PathBuf> {
+        let mut dirs = Vec::new();
+
+        match self.loading.last().and_then(|f| f.parent()) {
+            Some(d) => dirs.push(d.to_path_buf()),
+            None => dirs.push(PathBuf::from(".")),
+        };
+        dirs.extend(self.search_paths.iter().cloned());
+
+        for d in dirs {
+            let candidate = d.join(path);
+
+            if candidate.is_file() {
+                return candidate.canonicalize().ok();
+            }
+
+            if candidate.extension().is_none() {
+                let candidate = candidate.with_extension(SCRIPT_EXTENSION);
+                if candidate.is_file() {
+                    return candidate.canonicalize().ok();
+                }
+            }
+        }
+
+        None
+    }
+
+    fn load_module(&mut self, file : &PathBuf) -> Result<String, RuntimeError> {
+        let Ok(text) = std::fs::read_to_string(file) else {
+            return Err(RuntimeError::new(ErrorKind::Import, format!("can not read {}", file.display())));
+        };
+
+        let tree = match self.syntax.clone().parse(&text) {
+            Ok(t) => t,
+            Err(e) => return Err(RuntimeError::new(ErrorKind::Import, format!("{} in {}", e, file.display()))),
+        };
+
+        // two files with the same name still need different namespaces, also when one imports the other
+        let base = module_name(file);
+        let mut module = base.clone();
+        let mut n = 1;
+        while self.modules.values().any(|m| *m == module) {
+            n += 1;
+            module = format!("{}_{}", base, n);
+        }
+
+        // reserved while it runs, removed again if it fails
+        self.modules.insert(file.clone(), module.clone());
+
+        // the top level of a module runs outside of any function, so its variables are globals
+        let locals = self.envs.split_off(1);
+        let caller = self.namespace.replace(module.clone());
+        self.loading.push(file.clone());
+
+        let mut ret = Ok(());
+        for i in tree {
+            if let Err(e) = i.eval(self) {
+                ret = Err(e);
+                break;
+            }
+        }
+
+        self.loading.pop();
+        self.namespace = caller;
+        self.envs.extend(locals);
+
+        if let Err(e) = ret {
+            self.modules.remove(file);
+            return Err(e);
+        }
+
+        Ok(module)
+    }
+
+    /// `alias.name` for a global of an imported module, None if alias is not an import
+    pub fn module_global(&self, alias : &String, name : &String) -> Option<Result<Value, RuntimeError>> {
+        let module = self.aliases.get(&(self.namespace.clone(), alias.clone()))?;
+
+        Some(match self.envs[0].get(&format!("{}.{}", module, name)) {
+            Some(v) => Ok(v.clone()),
+            None => Err(RuntimeError::new(ErrorKind::Name, format!("Variable: {}.{} Not Found", alias, name))),
+        })
+    }
+}
+
+/// the file name without extension, usable as a namespace
+pub fn module_name(file : &Path) -> String {
+    let stem = file.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    stem.replace('.', "_")
+}
+
+#[test]
+fn test() -> () {
+    use super::run::Runtime;
+
+    let dir = std::env::temp_dir().join(format!("module_test_{}", std::process::id()));
+    let write = |name : &str, text : &str| {
+        let p = dir.join(name);
+        std::fs::create_dir_all(p.parent().unwrap()).unwrap();
+        std::fs::write(p, text).unwrap();
+    };
+
+    write("lib/math.txt", "displn(\"loading math\");\nbase = 10;\nfn scale(x) { x * base };\nfn twice(x) { scale(x) * 2 };\n");
+    write("lib/a.txt", "import \"b\";\n");
+    write("lib/b.txt", "import \"a.txt\";\n");
+    write("extra/util.txt", "fn shout(s) { s + \"!\" };\n");
+    write("a/util.txt", "import \"../b/util\" as inner;\nfn hi() { \"a\" };\nfn inner_hi() { inner.hi() };\n");
+    write("b/util.txt", "fn hi() { \"b\" };\n");
+
+    let mut r = Runtime::new();
+    r.capture_output();
+    r.add_search_path(dir.join("extra"));
+
+    let main = "import \"lib/math\";\nimport \"lib/math.txt\" as m;\nimport util;\nbase = 1;\ndispln(math.scale(2), m.twice(1), math.base, base, util.shout(\"hi\"));\n";
+    r.run_script(main, &dir.join("main.txt")).unwrap();
+    assert_eq!(r.take_output(), "loading math\n20\n20\n10\n1\nhi!\n");
+
+    let e = r.run_script("import \"lib/a\";", &dir.join("main.txt")).unwrap_err();
+    assert!(e.message.starts_with("import cycle:"));
+
+    // a module imported while another one of the same name is still loading gets its own namespace
+    r.run_script("import \"a/util\" as u;\ndispln(u.hi(), u.inner_hi());", &dir.join("main.txt")).unwrap();
+    assert_eq!(r.take_output(), "a\nb\n");
+
+    let e = r.run_script("import missing;", &dir.join("main.txt")).unwrap_err();
+    assert_eq!(e.message, "module missing not found");
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
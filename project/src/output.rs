@@ -0,0 +1,75 @@
+use std::cell::RefCell;
+use std::io::Write;
+use std::rc::Rc;
+
+/// an in memory sink that can be cloned, every clone writes into the same buffer. \
+/// used to capture program output or diagnostics
+#[derive(Clone, Default)]
+pub struct SharedBuffer {
+    buf : Rc<RefCell<Vec<u8>>>,
+}
+
+impl SharedBuffer {
+    pub fn new() -> SharedBuffer {
+        SharedBuffer::default()
+    }
+
+    /// returns everything written so far and clears the buffer
+    pub fn take(&self) -> String {
+        let bytes = std::mem::take(&mut *self.buf.borrow_mut());
+        String::from_utf8_lossy(&bytes).into_owned()
+    }
+
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.buf.borrow()).into_owned()
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, data : &[u8]) -> std::io::Result<usize> {
+        self.buf.borrow_mut().extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// writes everything to both sinks
+pub struct Tee<A : Write, B : Write> {
+    pub first : A,
+    pub second : B,
+}
+
+impl<A : Write, B : Write> Tee<A, B> {
+    pub fn new(first : A, second : B) -> Tee<A, B> {
+        Tee { first, second }
+    }
+}
+
+impl<A : Write, B : Write> Write for Tee<A, B> {
+    fn write(&mut self, data : &[u8]) -> std::io::Result<usize> {
+        self.first.write_all(data)?;
+        self.second.write_all(data)?;
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.first.flush()?;
+        self.second.flush()
+    }
+}
+
+#[test]
+fn test() -> () {
+    let a = SharedBuffer::new();
+    let b = SharedBuffer::new();
+
+    let mut t = Tee::new(a.clone(), b.clone());
+    write!(t, "hello {}", 1).unwrap();
+
+    assert_eq!(a.take(), "hello 1");
+    assert_eq!(a.contents(), "");
+    assert_eq!(b.contents(), "hello 1");
+}
@@ -1,10 +1,11 @@
 #![allow(unused)]
 
-use super::ast::{Ast, Vast, Bast, Forkop};
+use super::ast::{Ast, Vast, Bast, Forkop, Span, Param, Pattern, Arm};
 use super::value::{Value, FLOATING_PRECISION};
 
 use santiago::grammar::Associativity;
 use santiago::grammar::Grammar;
+use santiago::lexer::LexerRules;
 
 use rug::Integer;
 use rug::Float;
@@ -13,20 +14,28 @@ use std::str::FromStr;
 
 #[derive(Debug)]
 pub enum PR {
-    NameList        (Vec<String>),
+    Params          (Vec<Param>),
+    Param           (Param),
     Vast            (Vec<Ast>),
-    Name            (String),
+    Name            (String, Span),
 
     Ast             (Ast),
     Literal         (Value),
-
+    Pattern         (Pattern),
+    Patterns        (Vec<Pattern>),
+    Fields          (Vec<(String, Pattern)>),
+    Arm             (Arm),
+    Arms            (Vec<Arm>),
+
+    /// a punctuation lexeme whose position is kept, `:` and `=>`
+    At              (Span),
     Marker,
     None,
 }
 
 impl PR {
     pub const EMPTY_VAST : PR = PR::Vast(vec![]);
-    pub const EMPTY_NAMELIST : PR = PR::NameList(vec![]);
+    pub const EMPTY_PARAMS : PR = PR::Params(vec![]);
 
     pub fn take(&mut self) -> Self {
         let mut t = PR::None;
@@ -34,9 +43,58 @@ impl PR {
         return t;
     }
 
-    pub fn force_namelist(&mut self) -> Vec<String> {
+    pub fn force_params(&mut self) -> Vec<Param> {
+        match self.take() {
+            PR::Params(x) => x,
+            _ => panic!(),
+        }
+    }
+
+    pub fn force_param(&mut self) -> Param {
+        match self.take() {
+            PR::Param(x) => x,
+            _ => panic!(),
+        }
+    }
+
+    pub fn force_literal(&mut self) -> Value {
+        match self.take() {
+            PR::Literal(x) => x,
+            _ => panic!(),
+        }
+    }
+
+    pub fn force_pattern(&mut self) -> Pattern {
+        match self.take() {
+            PR::Pattern(x) => x,
+            _ => panic!(),
+        }
+    }
+
+    pub fn force_patterns(&mut self) -> Vec<Pattern> {
+        match self.take() {
+            PR::Patterns(x) => x,
+            _ => panic!(),
+        }
+    }
+
+    pub fn force_fields(&mut self) -> Vec<(String, Pattern)> {
         match self.take() {
-            PR::NameList(x) => x,
+            PR::Fields(x) => x,
+            _ => panic!(),
+        }
+    }
+
+    pub fn force_arm(&mut self) -> Arm {
+        match self.take() {
+            PR::Arm(x) => x,
+            _ => panic!(),
+        }
+    }
+
+    pub fn force_arms(&mut self) -> Vec<Arm> {
+        match self.take() {
+            PR::Arms(x) => x,
             _ => panic!(),
         }
     }
@@ -56,8 +114,19 @@ impl PR {
     }
 
     pub fn force_name(&mut self) -> String {
+        self.force_spanned_name().0
+    }
+
+    pub fn force_spanned_name(&mut self) -> (String, Span) {
         match self.take() {
-            PR::Name(x) => x,
+            PR::Name(x, s) => (x, s),
+            _ => panic!(),
+        }
+    }
+
+    pub fn force_span(&mut self) -> Span {
+        match self.take() {
+            PR::At(s) => s,
             _ => panic!(),
         }
     }
@@ -67,6 +136,78 @@ impl PR {
     }
 }
 
+/// the lexer rules and the grammar, building them is slow so they are built once and shared
+pub struct Syntax {
+    pub lexer : LexerRules,
+    pub grammar : Grammar<PR>,
+}
+
+impl Default for Syntax {
+    fn default() -> Syntax {
+        Syntax::new()
+    }
+}
+
+impl Syntax {
+    pub fn new() -> Syntax {
+        Syntax { lexer : super::lexer::rules(), grammar : grammar() }
+    }
+
+    /// the top level expressions of `text`, Err holds a message for the user
+    pub fn parse(&self, text : &str) -> Result<Vast, String> {
+        let Ok(lexemes) = santiago::lexer::lex(&self.lexer, text) else {
+            return Err(format!("Invalid Syntax!"));
+        };
+
+        // precedence can rule out every tree, that is a syntax error too
+        let Some(tree) = santiago::parser::parse(&self.grammar, &lexemes).ok().and_then(|t| t.into_iter().next()) else {
+            return Err(format!("Invalid Structure of Program!"));
+        };
+
+        Ok(tree.as_abstract_syntax_tree().force_vast())
+    }
+
+    /// where `text` stops being valid, None when it parses. past the last character when it ends too early
+    pub fn error_at(&self, text : &str) -> Option<Span> {
+        let lexemes = match santiago::lexer::lex(&self.lexer, text) {
+            Ok(l) => l,
+            Err(e) => return Some(Span { line : e.position.line, column : e.position.column }),
+        };
+
+        let e = santiago::parser::parse(&self.grammar, &lexemes).err()?;
+
+        // the parser reports the last lexeme it accepted, the one after it is the mistake
+        let next = match e.at {
+            Some(at) => lexemes.iter().position(|l| std::rc::Rc::ptr_eq(l, &at)).and_then(|i| lexemes.get(i + 1)),
+            None => lexemes.first(),
+        };
+
+        match next {
+            Some(l) => Some(Span { line : l.position.line, column : l.position.column }),
+            None => {
+                let last = text.rsplit('\n').next().unwrap_or("");
+                Some(Span { line : text.split('\n').count(), column : last.chars().count() + 1 })
+            },
+        }
+    }
+}
+
+/// `name = expr` in an argument list is a keyword argument, not an assignment
+pub fn split_kwargs(with : Vast) -> (Vast, Vec<(String, Ast)>) {
+    let mut args = Vec::with_capacity(with.len());
+    let mut kwargs = Vec::new();
+
+    for a in with {
+        match a {
+            Ast::Set { name, value, .. } => kwargs.push((name, *value)),
+            _ => args.push(a),
+        }
+    }
+
+    kwargs.reverse();
+    (args, kwargs)
+}
+
 pub fn rev<T>(mut to_rev : Vec<T>) -> Vec<T> {
     to_rev.reverse();
     to_rev
@@ -83,30 +224,97 @@ pub fn grammar() -> Grammar<PR> {
 
         "expr"      => rules "(" "expr" ")" => |mut t| {t[1].take()};
 
-        "expr"      => rules "name"         => |mut t| {Ast::Get(t[0].force_name()).pr()};
+        "expr"      => rules "name"         => |mut t| {let (name, span) = t[0].force_spanned_name(); Ast::Get(name, span).pr()};
         "expr"      => rules "$" "[" "arg_list" "]" => |mut t| {Ast::VecLiteral(rev(t[2].force_vast())).pr()};
-        "expr"      => rules "fn" "name" "(" "name_list" ")" "expr" => |mut t| {Ast::FunDef{args : rev(t[3].force_namelist()), body: t[5].force_bast(), name: t[1].force_name()}.pr()};
-        "expr"      => rules "name" "=" "expr" => |mut t| {Ast::Set{name : t[0].force_name(), value : t[2].force_bast()}.pr()};
+        "expr"      => rules "$" "{" "arg_list" "}" => |mut t| {Ast::SetLiteral(rev(t[2].force_vast())).pr()};
+        "expr"      => rules "fn" "name" "(" "param_list" ")" "expr" => |mut t| {let (name, span) = t[1].force_spanned_name(); Ast::FunDef{args : rev(t[3].force_params()), body: t[5].force_bast(), name, span}.pr()};
+        "expr"      => rules "name" "=" "expr" => |mut t| {let (name, span) = t[0].force_spanned_name(); Ast::Set{name, value : t[2].force_bast(), span}.pr()};
         
         "expr"      => rules "[" "expr_list" "]" => |mut t| {Ast::ExpressionList(rev(t[1].force_vast())).pr()};
 
-        "name_list" => rules "name" "," "name_list" => |mut t| {let mut a = t[2].force_namelist(); a.push(t[0].force_name()); PR::NameList(a)};
-        "name_list" => rules "name"                 => |mut t| {PR::NameList(vec![t[0].force_name()])};
-        "name_list" => empty                        => |_|  {PR::EMPTY_NAMELIST};
-
-        "expr"      => rules "name" "(" "arg_list" ")"  => |mut t| {Ast::Call{name : t[0].force_name(), with : t[2].force_vast()}.pr()};
+        "param_list"=> rules "param" "," "param_list" => |mut t| {let mut a = t[2].force_params(); a.push(t[0].force_param()); PR::Params(a)};
+        "param_list"=> rules "param"                => |mut t| {PR::Params(vec![t[0].force_param()])};
+        "param_list"=> empty                        => |_|  {PR::EMPTY_PARAMS};
+        "param"     => rules "name"                 => |mut t| {let (name, span) = t[0].force_spanned_name(); PR::Param(Param{name, default : None, rest : false, span})};
+        "param"     => rules "name" "=" "expr"      => |mut t| {let (name, span) = t[0].force_spanned_name(); PR::Param(Param{name, default : Some(t[2].force_ast()), rest : false, span})};
+        "param"     => rules "..." "name"           => |mut t| {let (name, span) = t[1].force_spanned_name(); PR::Param(Param{name, default : None, rest : true, span})};
+
+        "expr"      => rules "name" "(" "arg_list" ")"  => |mut t| {
+            let (name, span) = t[0].force_spanned_name();
+            let (with, kwargs) = split_kwargs(t[2].force_vast());
+            Ast::Call{name, with, kwargs, span}.pr()
+        };
         "arg_list"  => rules "expr" "," "arg_list"  => |mut t| {let mut v = t[2].force_vast(); v.push(t[0].force_ast()); PR::Vast(v)};
         "arg_list"  => rules "expr"                 => |mut t| {PR::Vast(vec![t[0].force_ast()])};
         "arg_list"  => empty                        => |_| {PR::EMPTY_VAST};
 
         "expr"       => rules "{" "conditional" "}"     => |mut t| {t[1].take()};
-        "conditional"=> rules "expr" ":" "expr" "," "conditional" => |mut t| {Ast::If{else_ : Some(t[4].force_bast()), if_ : t[0].force_bast(), then : t[2].force_bast()}.pr()};
-        "conditional"=> rules "expr" ":" "expr" => |mut t| {Ast::If{else_ : Some(Ast::NULL.boxed()), if_ : t[0].force_bast(), then : t[2].force_bast()}.pr()};
+        "conditional"=> rules "expr" ":" "expr" "," "conditional" => |mut t| {Ast::If{else_ : Some(t[4].force_bast()), if_ : t[0].force_bast(), then : t[2].force_bast(), span : t[1].force_span()}.pr()};
+        "conditional"=> rules "expr" ":" "expr" => |mut t| {Ast::If{else_ : Some(Ast::NULL.boxed()), if_ : t[0].force_bast(), then : t[2].force_bast(), span : t[1].force_span()}.pr()};
         "conditional"=> rules "expr" => |mut t| {t[0].take()};
         "conditional"=> empty       => |_| {Ast::NULL.pr()};
 
         "expr"      => rules "while" "expr" ":" "expr"  => |mut t| {Ast::While{body : t[3].force_bast(), cond : t[1].force_bast()}.pr()};
 
+        "expr"      => rules "throw" "expr"         => |mut t| {Ast::Throw(t[1].force_bast()).pr()};
+        "expr"      => rules "try" "expr" "catch" "(" "name" ")" "expr" => |mut t| {Ast::Try{body : t[1].force_bast(), name : t[4].force_name(), handler : t[6].force_bast()}.pr()};
+        "expr"      => rules "expr" "." "name"      => |mut t| {Ast::Field{target : t[0].force_bast(), field : t[2].force_name()}.pr()};
+        "expr"      => rules "name" "." "name" "(" "arg_list" ")" => |mut t| {
+            let (module, span) = t[0].force_spanned_name();
+            let (with, kwargs) = split_kwargs(t[4].force_vast());
+            Ast::Call{name : format!("{}.{}", module, t[2].force_name()), with, kwargs, span}.pr()
+        };
+
+        "expr"      => rules "import" "path"        => |mut t| {Ast::Import{path : t[1].force_name(), alias : None}.pr()};
+        "expr"      => rules "import" "path" "as" "name" => |mut t| {Ast::Import{path : t[1].force_name(), alias : Some(t[3].force_name())}.pr()};
+        "path"      => rules "name"                 => |mut t| {t[0].take()};
+
+        "expr"      => rules "struct" "name" "{" "param_list" "}" => |mut t| {let (name, span) = t[1].force_spanned_name(); Ast::StructDef{fields : rev(t[3].force_params()), name, span}.pr()};
+        // the subscript is its own rule, otherwise the precedence of "[" would apply to it too and reject `xs[i - 1]`
+        "expr"      => rules "expr" "[" "subscript" "]" => |mut t| {Ast::Index{target : t[0].force_bast(), index : t[2].force_bast()}.pr()};
+        "subscript" => rules "expr"                 => |mut t| {t[0].take()};
+        // "index[" and "field." have no precedence of their own, so these assignments bind like "=" and not like an access
+        "expr"      => rules "expr" "index[" "expr" "]" "=" "expr" => |mut t| {Ast::SetIndex{target : t[0].force_bast(), index : t[2].force_bast(), value : t[5].force_bast()}.pr()};
+        "expr"      => rules "expr" "+=" "expr" => |mut t| {Ast::Update{target : t[0].force_bast(), op : Forkop::Add, value : t[2].force_bast()}.pr()};
+        "expr"      => rules "expr" "-=" "expr" => |mut t| {Ast::Update{target : t[0].force_bast(), op : Forkop::Sub, value : t[2].force_bast()}.pr()};
+        "expr"      => rules "expr" "*=" "expr" => |mut t| {Ast::Update{target : t[0].force_bast(), op : Forkop::Mul, value : t[2].force_bast()}.pr()};
+        "expr"      => rules "expr" "/=" "expr" => |mut t| {Ast::Update{target : t[0].force_bast(), op : Forkop::Div, value : t[2].force_bast()}.pr()};
+        "expr"      => rules "expr" "%=" "expr" => |mut t| {Ast::Update{target : t[0].force_bast(), op : Forkop::Mod, value : t[2].force_bast()}.pr()};
+        "expr"      => rules "expr" "^=" "expr" => |mut t| {Ast::Update{target : t[0].force_bast(), op : Forkop::Pow, value : t[2].force_bast()}.pr()};
+        "expr"      => rules "expr" "field." "name" "=" "expr" => |mut t| {Ast::SetField{target : t[0].force_bast(), field : t[2].force_name(), value : t[4].force_bast()}.pr()};
+
+        "expr"      => rules "match" "expr" "{" "arms" "}" => |mut t| {Ast::Match{value : t[1].force_bast(), arms : rev(t[3].force_arms())}.pr()};
+        "arms"      => rules "arm" "," "arms"       => |mut t| {let mut a = t[2].force_arms(); a.push(t[0].force_arm()); PR::Arms(a)};
+        "arms"      => rules "arm"                  => |mut t| {PR::Arms(vec![t[0].force_arm()])};
+        "arms"      => empty                        => |_| {PR::Arms(vec![])};
+        "arm"       => rules "pattern" "=>" "expr"  => |mut t| {PR::Arm(Arm{pattern : t[0].force_pattern(), guard : None, body : t[2].force_ast(), span : t[1].force_span()})};
+        "arm"       => rules "pattern" "if" "expr" "=>" "expr" => |mut t| {PR::Arm(Arm{pattern : t[0].force_pattern(), guard : Some(t[2].force_ast()), body : t[4].force_ast(), span : t[3].force_span()})};
+
+        "pattern"   => rules "name"                 => |mut t| {PR::Pattern(match t[0].force_name() {
+            x if x == "_" => Pattern::Wildcard,
+            x => Pattern::Bind(x),
+        })};
+        "pattern"   => rules "number"               => |mut t| {PR::Pattern(Pattern::Literal(t[0].force_literal()))};
+        "pattern"   => rules "number" ".." "number" => |mut t| {PR::Pattern(Pattern::Range{from : t[0].force_literal(), to : t[2].force_literal()})};
+        "pattern"   => rules "list_pattern"         => |mut t| {t[0].take()};
+        "pattern"   => rules "struct_pattern"       => |mut t| {t[0].take()};
+        "list_pattern"  => rules "[" "]"            => |_| {PR::Pattern(Pattern::List{items : vec![], rest : None})};
+        "list_pattern"  => rules "[" "patterns" "]" => |mut t| {PR::Pattern(Pattern::List{items : rev(t[1].force_patterns()), rest : None})};
+        "list_pattern"  => rules "[" "patterns" "," "..." "name" "]" => |mut t| {PR::Pattern(Pattern::List{items : rev(t[1].force_patterns()), rest : Some(t[4].force_name())})};
+        "list_pattern"  => rules "[" "..." "name" "]" => |mut t| {PR::Pattern(Pattern::List{items : vec![], rest : Some(t[2].force_name())})};
+        "struct_pattern"=> rules "name" "{" "field_patterns" "}" => |mut t| {PR::Pattern(Pattern::Struct{name : t[0].force_name(), fields : rev(t[2].force_fields())})};
+        "field_patterns"=> rules "field_pattern" "," "field_patterns" => |mut t| {let mut a = t[2].force_fields(); a.append(&mut t[0].force_fields()); PR::Fields(a)};
+        "field_patterns"=> rules "field_pattern"    => |mut t| {t[0].take()};
+        "field_patterns"=> empty                    => |_| {PR::Fields(vec![])};
+        "field_pattern" => rules "name"             => |mut t| {let n = t[0].force_name(); PR::Fields(vec![(n.clone(), Pattern::Bind(n))])};
+        "field_pattern" => rules "name" ":" "pattern" => |mut t| {PR::Fields(vec![(t[0].force_name(), t[2].force_pattern())])};
+
+        "expr"      => rules "list_pattern" "=" "expr"   => |mut t| {Ast::Destructure{pattern : t[0].force_pattern(), value : t[2].force_bast()}.pr()};
+        "expr"      => rules "struct_pattern" "=" "expr" => |mut t| {Ast::Destructure{pattern : t[0].force_pattern(), value : t[2].force_bast()}.pr()};
+        "patterns"  => rules "pattern" "," "patterns" => |mut t| {let mut a = t[2].force_patterns(); a.push(t[0].force_pattern()); PR::Patterns(a)};
+        "patterns"  => rules "pattern"              => |mut t| {PR::Patterns(vec![t[0].force_pattern()])};
+        "number"    => rules "-" "number"           => |mut t| {PR::Literal(Value::Integer(rug::Integer::new()).sub(t[1].force_literal()).unwrap())};
+
         "expr"      => rules "expr" "<" "expr" => |mut t| {Ast::Fork{left : t[0].force_bast(), right : t[2].force_bast(), op : Forkop::Lt}.pr()};
         "expr"      => rules "expr" ">" "expr" => |mut t| {Ast::Fork{left : t[0].force_bast(), right : t[2].force_bast(), op : Forkop::Gt}.pr()};
         "expr"      => rules "expr" "<=" "expr"=> |mut t| {Ast::Fork{left : t[0].force_bast(), right : t[2].force_bast(), op : Forkop::Lte}.pr()};
@@ -114,6 +322,10 @@ pub fn grammar() -> Grammar<PR> {
         "expr"      => rules "expr" "==" "expr"=> |mut t| {Ast::Fork{left : t[0].force_bast(), right : t[2].force_bast(), op : Forkop::Eql}.pr()};
         "expr"      => rules "expr" "!=" "expr"=> |mut t| {Ast::Fork{left : t[0].force_bast(), right : t[2].force_bast(), op : Forkop::Neq}.pr()};
 
+        "expr"      => rules "expr" "in" "expr" => |mut t| {Ast::Fork{left : t[0].force_bast(), right : t[2].force_bast(), op : Forkop::In}.pr()};
+        "expr"      => rules "expr" "|" "expr" => |mut t| {Ast::Fork{left : t[0].force_bast(), right : t[2].force_bast(), op : Forkop::Union}.pr()};
+        "expr"      => rules "expr" "&" "expr" => |mut t| {Ast::Fork{left : t[0].force_bast(), right : t[2].force_bast(), op : Forkop::Intersect}.pr()};
+
         "expr"      => rules "expr" "+" "expr" => |mut t| {Ast::Fork{left : t[0].force_bast(), right : t[2].force_bast(), op : Forkop::Add}.pr()};
         "expr"      => rules "expr" "-" "expr" => |mut t| {Ast::Fork{left : t[0].force_bast(), right : t[2].force_bast(), op : Forkop::Sub}.pr()};
         "expr"      => rules "expr" "*" "expr" => |mut t| {Ast::Fork{left : t[0].force_bast(), right : t[2].force_bast(), op : Forkop::Mul}.pr()};
@@ -122,7 +334,7 @@ pub fn grammar() -> Grammar<PR> {
         "expr"      => rules "expr" "%" "expr" => |mut t| {Ast::Fork{left : t[0].force_bast(), right : t[2].force_bast(), op : Forkop::Mod}.pr()};
 
         // Lexeme ports
-        "name"      =>  lexemes "NAME"      => |t| { PR::Name(t[0].raw.clone()) };
+        "name"      =>  lexemes "NAME"      => |t| { PR::Name(t[0].raw.clone(), Span { line : t[0].position.line, column : t[0].position.column }) };
         "expr"      =>  lexemes "STRING"    => |t| { PR::Ast({
             let x = t[0].raw.clone();
             let raw = x.as_str();
@@ -131,15 +343,37 @@ pub fn grammar() -> Grammar<PR> {
 
             Ast::Literal(Value::String(String::from(final_)))
         }) };
-        "expr"      =>  lexemes "INTEGER"   => |t| { PR::Ast(Ast::Literal(Value::Integer(rug::Integer::from_str(&t[0].raw.as_str()).unwrap()))) };
+        // r"..." and r#"..."# (which may contain quotes), meant for regex patterns
+        "expr"      =>  lexemes "RAW_STRING" => |t| {
+            let raw = t[0].raw.as_str();
+            let hashes = if raw.starts_with("r#") {1} else {0};
+            PR::Ast(Ast::Literal(Value::String(String::from(&raw[2 + hashes..raw.len() - 1 - hashes]))))
+        };
+        "path"      =>  lexemes "STRING"    => |t| {
+            let raw = t[0].raw.as_str();
+            PR::Name(String::from(&raw[1..raw.len()-1]), Span { line : t[0].position.line, column : t[0].position.column })
+        };
+        "pattern"   =>  lexemes "STRING"    => |t| {
+            let raw = t[0].raw.as_str();
+            PR::Pattern(Pattern::Literal(Value::String(String::from(&raw[1..raw.len()-1]))))
+        };
+        "number"    =>  lexemes "INTEGER"   => |t| { PR::Literal(Value::Integer(rug::Integer::from_str(t[0].raw.as_str()).unwrap())) };
+        "number"    =>  lexemes "FLOAT"     => |t| { PR::Literal(Value::Float(Float::with_val(FLOATING_PRECISION, Float::parse(t[0].raw.as_str()).unwrap()))) };
+        "expr"      =>  lexemes "INTEGER"   => |t| { PR::Ast(Ast::Literal(Value::Integer(rug::Integer::from_str(t[0].raw.as_str()).unwrap()))) };
         "expr"      =>  lexemes "FLOAT"     => |t| { PR::Ast(Ast::Literal(Value::Float(
             {
-                Float::with_val(FLOATING_PRECISION, Float::parse(&t[0].raw.as_str()).unwrap())
+                Float::with_val(FLOATING_PRECISION, Float::parse(t[0].raw.as_str()).unwrap())
             }
         ))) };
 
         "="         =>  lexemes "="         => |_| { PR::Marker };
         "+"         =>  lexemes "+"         => |_| { PR::Marker };
+        "+="        =>  lexemes "+="        => |_| { PR::Marker };
+        "-="        =>  lexemes "-="        => |_| { PR::Marker };
+        "*="        =>  lexemes "*="        => |_| { PR::Marker };
+        "/="        =>  lexemes "/="        => |_| { PR::Marker };
+        "%="        =>  lexemes "%="        => |_| { PR::Marker };
+        "^="        =>  lexemes "^="        => |_| { PR::Marker };
         "-"         =>  lexemes "-"         => |_| { PR::Marker };
         "*"         =>  lexemes "*"         => |_| { PR::Marker };
         "/"         =>  lexemes "/"         => |_| { PR::Marker };
@@ -151,25 +385,46 @@ pub fn grammar() -> Grammar<PR> {
         ">="        =>  lexemes ">="        => |_| { PR::Marker };
         "=="        =>  lexemes "=="        => |_| { PR::Marker };
         "!="        =>  lexemes "!="        => |_| { PR::Marker };
+        "in"        =>  lexemes "IN"        => |_| { PR::Marker };
+        "|"         =>  lexemes "|"         => |_| { PR::Marker };
+        "&"         =>  lexemes "&"         => |_| { PR::Marker };
 
-        ":"         =>  lexemes ":"         => |_| { PR::Marker };
+        ":"         =>  lexemes ":"         => |t| { PR::At(Span { line : t[0].position.line, column : t[0].position.column }) };
         ","         =>  lexemes ","         => |_| { PR::Marker };
         "("         =>  lexemes "("         => |_| { PR::Marker };
         ")"         =>  lexemes ")"         => |_| { PR::Marker };
         "{"         =>  lexemes "{"         => |_| { PR::Marker };
         "}"         =>  lexemes "}"         => |_| { PR::Marker };
         "["         =>  lexemes "["         => |_| { PR::Marker };
+        "index["    =>  lexemes "["         => |_| { PR::Marker };
         "]"         =>  lexemes "]"         => |_| { PR::Marker };
         "fn"        =>  lexemes "FN"        => |_| { PR::Marker };
         "$"         =>  lexemes "$"         => |_| { PR::Marker };
         ";"         =>  lexemes ";"         => |_| { PR::Marker };
         "while"     =>  lexemes "WHILE"     => |_| { PR::Marker };
-
-        Associativity::Right => rules "=";
-        Associativity::Left => rules "<=" ">=" "==" "!=" ">" "<";
+        "try"       =>  lexemes "TRY"       => |_| { PR::Marker };
+        "catch"     =>  lexemes "CATCH"     => |_| { PR::Marker };
+        "throw"     =>  lexemes "THROW"     => |_| { PR::Marker };
+        "."         =>  lexemes "."         => |_| { PR::Marker };
+        "field."    =>  lexemes "."         => |_| { PR::Marker };
+        "..."       =>  lexemes "..."       => |_| { PR::Marker };
+        "import"    =>  lexemes "IMPORT"    => |_| { PR::Marker };
+        "as"        =>  lexemes "AS"        => |_| { PR::Marker };
+        "struct"    =>  lexemes "STRUCT"    => |_| { PR::Marker };
+        "match"     =>  lexemes "MATCH"     => |_| { PR::Marker };
+        "if"        =>  lexemes "IF"        => |_| { PR::Marker };
+        "=>"        =>  lexemes "=>"        => |t| { PR::At(Span { line : t[0].position.line, column : t[0].position.column }) };
+        ".."        =>  lexemes ".."        => |_| { PR::Marker };
+
+        // like assignments, throw and the handler of try extend as far right as they can
+        Associativity::Right => rules "=" "+=" "-=" "*=" "/=" "%=" "^=" "throw" "try";
+        Associativity::Left => rules "<=" ">=" "==" "!=" ">" "<" "in";
+        Associativity::Left => rules "|";
+        Associativity::Left => rules "&";
         Associativity::Left => rules "+" "-";
         Associativity::Left => rules "*" "/";
         Associativity::Left => rules "%" "^";
+        Associativity::Left => rules "." "[";
     )
 }
 
@@ -201,10 +456,30 @@ fn test_me() -> () {
     let l = rules();
     let g = grammar();
 
-    let lexemes = santiago::lexer::lex(&l, &text).unwrap();
+    let lexemes = santiago::lexer::lex(&l, text).unwrap();
     let trees = santiago::parser::parse(&g, &lexemes).unwrap();
     println!("LENGTH {}", trees.len());
     let tree = trees[0].as_abstract_syntax_tree();
 
     println!("{:#?}", tree);
-}
\ No newline at end of file
+}
+#[test]
+fn test_assignment_targets() -> () {
+    let syntax = Syntax::new();
+
+    // the whole right hand side is assigned, not just its first operand
+    for text in ["p.x = a + b;", "xs[i] = a + b;", "xs[i][j] = a + b;", "xs[i - 1].x = a + b;"] {
+        let tree = syntax.parse(text).unwrap();
+        match &tree[..] {
+            [Ast::SetField { value, .. }] | [Ast::SetIndex { value, .. }] => assert!(matches!(**value, Ast::Fork { op : Forkop::Add, .. }), "{}", text),
+            _ => panic!("{} parsed as {:?}", text, tree),
+        }
+    }
+
+    // operators inside brackets, and indexing binds tighter than them outside
+    let tree = syntax.parse("a + xs[i - 1];").unwrap();
+    match &tree[..] {
+        [Ast::Fork { op : Forkop::Add, right, .. }] => assert!(matches!(&**right, Ast::Index { index, .. } if matches!(**index, Ast::Fork { op : Forkop::Sub, .. }))),
+        _ => panic!("parsed as {:?}", tree),
+    }
+}
@@ -0,0 +1,338 @@
+use rug::Float;
+
+use super::ast::{Arm, Ast, Forkop, Param, Pattern, Vast};
+use super::value::{Value, FLOATING_PRECISION};
+
+const INDENT : &str = "    ";
+
+/// conditionals longer than this are split over several lines
+const WIDTH : usize = 60;
+
+// how tightly an expression binds, operands that bind looser than their context get parentheses
+const ASSIGN : u8 = 1;
+const COMPARE : u8 = 2;
+const UNION : u8 = 3;
+const INTERSECT : u8 = 4;
+const SUM : u8 = 5;
+const PRODUCT : u8 = 6;
+const POWER : u8 = 7;
+const POSTFIX : u8 = 8;
+const ATOM : u8 = 9;
+
+/// source text for a parsed program, one top level expression per line
+pub fn program(tree : &Vast) -> String {
+    let mut out = String::new();
+
+    for a in tree {
+        out.push_str(&expr(a, 0, 0));
+        out.push_str(";\n");
+    }
+
+    out
+}
+
+/// source text for `a`, lines after the first are indented by `depth` levels
+pub fn expr(a : &Ast, context : u8, depth : usize) -> String {
+    let text = match a {
+        Ast::Literal(v) => literal(v),
+        Ast::Get(name, _) => name.clone(),
+        Ast::VecLiteral(v) => format!("$[{}]", list(v, depth)),
+        Ast::SetLiteral(v) => format!("${{{}}}", list(v, depth)),
+        Ast::FunDef { name, args, body, .. } => format!("fn {}({}) {}", name, params(args, depth), block(body, depth)),
+        Ast::Set { name, value, .. } => format!("{} = {}", name, expr(value, ASSIGN, depth)),
+        Ast::While { cond, body } => format!("while {} : {}", expr(cond, 0, depth), expr(body, ATOM, depth)),
+        Ast::ExpressionList(v) => {
+            if v.is_empty() {
+                return String::from("[]");
+            }
+
+            let inner = INDENT.repeat(depth + 1);
+            let mut out = String::from("[\n");
+            for a in v {
+                out.push_str(&format!("{}{};\n", inner, expr(a, 0, depth + 1)));
+            }
+            out.push_str(&format!("{}]", INDENT.repeat(depth)));
+            out
+        },
+        Ast::Call { name, with, kwargs, .. } => {
+            let mut parts : Vec<String> = with.iter().rev().map(|a| expr(a, ASSIGN, depth)).collect();
+            parts.extend(kwargs.iter().map(|(k, v)| format!("{} = {}", k, expr(v, ASSIGN, depth))));
+            format!("{}({})", name, parts.join(", "))
+        },
+        Ast::If { .. } => conditional(a, depth, false),
+        Ast::Fork { left, right, op } => {
+            let p = precedence(op);
+            format!("{} {} {}", expr(left, p, depth), operator(op), expr(right, p + 1, depth))
+        },
+        Ast::Throw(e) => format!("throw {}", expr(e, ASSIGN, depth)),
+        Ast::Try { body, name, handler } => format!("try {} catch ({}) {}", expr(body, 0, depth), name, expr(handler, ASSIGN, depth)),
+        Ast::Field { target, field } => format!("{}.{}", expr(target, POSTFIX, depth), field),
+        Ast::Import { path, alias } => {
+            let path = if is_name(path) { path.clone() } else { format!("\"{}\"", path) };
+            match alias {
+                Some(a) => format!("import {} as {}", path, a),
+                None => format!("import {}", path),
+            }
+        },
+        Ast::StructDef { name, fields, .. } => format!("struct {} {{ {} }}", name, params(fields, depth)),
+        Ast::SetField { target, field, value } => format!("{}.{} = {}", expr(target, POSTFIX, depth), field, expr(value, ASSIGN, depth)),
+        Ast::Index { target, index } => format!("{}[{}]", expr(target, POSTFIX, depth), expr(index, 0, depth)),
+        Ast::SetIndex { target, index, value } => format!("{}[{}] = {}", expr(target, POSTFIX, depth), expr(index, 0, depth), expr(value, ASSIGN, depth)),
+        Ast::Update { target, op, value } => format!("{} {}= {}", expr(target, POSTFIX, depth), operator(op), expr(value, ASSIGN, depth)),
+        Ast::Match { value, arms } => {
+            let inner = INDENT.repeat(depth + 1);
+            let mut out = format!("match {} {{\n", expr(value, 0, depth));
+            for (i, arm) in arms.iter().enumerate() {
+                let sep = if i + 1 == arms.len() { "" } else { "," };
+                out.push_str(&format!("{}{}{}\n", inner, self::arm(arm, depth + 1), sep));
+            }
+            out.push_str(&format!("{}}}", INDENT.repeat(depth)));
+            out
+        },
+        Ast::Destructure { pattern, value } => format!("{} = {}", self::pattern(pattern), expr(value, ASSIGN, depth)),
+    };
+
+    if binding(a) < context {
+        format!("({})", text)
+    } else {
+        text
+    }
+}
+
+fn binding(a : &Ast) -> u8 {
+    match a {
+        Ast::Fork { op, .. } => precedence(op),
+        Ast::Field { .. } | Ast::Index { .. } => POSTFIX,
+        Ast::Literal(_) | Ast::Get(..) | Ast::VecLiteral(_) | Ast::SetLiteral(_) | Ast::ExpressionList(_) | Ast::Call { .. } | Ast::If { .. } => ATOM,
+        // assignments and everything starting with a keyword extend as far right as they can
+        _ => ASSIGN,
+    }
+}
+
+fn precedence(op : &Forkop) -> u8 {
+    match op {
+        Forkop::Gt | Forkop::Lt | Forkop::Lte | Forkop::Gte | Forkop::Eql | Forkop::Neq | Forkop::In => COMPARE,
+        Forkop::Union => UNION,
+        Forkop::Intersect => INTERSECT,
+        Forkop::Add | Forkop::Sub => SUM,
+        Forkop::Mul | Forkop::Div => PRODUCT,
+        Forkop::Pow | Forkop::Mod => POWER,
+    }
+}
+
+pub fn operator(op : &Forkop) -> &'static str {
+    match op {
+        Forkop::Gt => ">",
+        Forkop::Lt => "<",
+        Forkop::Lte => "<=",
+        Forkop::Gte => ">=",
+        Forkop::Eql => "==",
+        Forkop::Neq => "!=",
+        Forkop::Add => "+",
+        Forkop::Sub => "-",
+        Forkop::Mul => "*",
+        Forkop::Div => "/",
+        Forkop::Pow => "^",
+        Forkop::Mod => "%",
+        Forkop::Union => "|",
+        Forkop::Intersect => "&",
+        Forkop::In => "in",
+    }
+}
+
+/// the first line of a function or struct definition, for editors
+pub fn signature(a : &Ast) -> Option<String> {
+    match a {
+        Ast::FunDef { name, args, .. } => Some(format!("fn {}({})", name, params(args, 0))),
+        Ast::StructDef { name, fields, .. } => Some(format!("struct {} {{ {} }}", name, params(fields, 0))),
+        _ => None,
+    }
+}
+
+fn list(v : &Vast, depth : usize) -> String {
+    v.iter().map(|a| expr(a, ASSIGN, depth)).collect::<Vec<String>>().join(", ")
+}
+
+fn params(v : &[Param], depth : usize) -> String {
+    v.iter().map(|p| match (&p.default, p.rest) {
+        (_, true) => format!("...{}", p.name),
+        (Some(d), _) => format!("{} = {}", p.name, expr(d, ASSIGN, depth)),
+        (None, _) => p.name.clone(),
+    }).collect::<Vec<String>>().join(", ")
+}
+
+/// a function body on lines of its own, anything but a block or a conditional is wrapped in braces
+fn block(body : &Ast, depth : usize) -> String {
+    match body {
+        Ast::If { .. } => conditional(body, depth, true),
+        Ast::ExpressionList(_) => expr(body, 0, depth),
+        _ => format!("{{\n{}{}\n{}}}", INDENT.repeat(depth + 1), expr(body, 0, depth + 1), INDENT.repeat(depth)),
+    }
+}
+
+/// `{ cond : then, cond : then, otherwise }`, on one line when it is short unless `split`
+fn conditional(a : &Ast, depth : usize, split : bool) -> String {
+    let mut branches = Vec::new();
+    let mut next = Some(a);
+
+    while let Some(a) = next {
+        next = None;
+        match a {
+            Ast::If { if_, then, else_, .. } => {
+                branches.push((Some(&**if_), &**then));
+                next = else_.as_deref();
+            },
+            // a conditional without an otherwise branch ends in nil
+            Ast::Literal(Value::Nil) if !branches.is_empty() => {},
+            _ => branches.push((None, a)),
+        }
+    }
+
+    let branch = |(cond, then) : &(Option<&Ast>, &Ast), depth : usize| match cond {
+        Some(c) => format!("{} : {}", expr(c, 0, depth), expr(then, 0, depth)),
+        None => expr(then, 0, depth),
+    };
+
+    let line = branches.iter().map(|b| branch(b, depth)).collect::<Vec<String>>().join(", ");
+    if line.is_empty() {
+        return String::from("{}");
+    }
+    if !split && !line.contains('\n') && line.len() <= WIDTH {
+        return format!("{{ {} }}", line);
+    }
+
+    let inner = INDENT.repeat(depth + 1);
+    let lines = branches.iter().map(|b| format!("{}{}", inner, branch(b, depth + 1))).collect::<Vec<String>>();
+    format!("{{\n{}\n{}}}", lines.join(",\n"), INDENT.repeat(depth))
+}
+
+fn arm(arm : &Arm, depth : usize) -> String {
+    match &arm.guard {
+        Some(g) => format!("{} if {} => {}", pattern(&arm.pattern), expr(g, 0, depth), expr(&arm.body, ASSIGN, depth)),
+        None => format!("{} => {}", pattern(&arm.pattern), expr(&arm.body, ASSIGN, depth)),
+    }
+}
+
+pub fn pattern(p : &Pattern) -> String {
+    match p {
+        Pattern::Wildcard => String::from("_"),
+        Pattern::Literal(v) => literal(v),
+        Pattern::Bind(name) => name.clone(),
+        Pattern::List { items, rest } => {
+            let mut parts = items.iter().map(pattern).collect::<Vec<String>>();
+            if let Some(r) = rest {
+                parts.push(format!("...{}", r));
+            }
+            format!("[{}]", parts.join(", "))
+        },
+        Pattern::Range { from, to } => format!("{}..{}", literal(from), literal(to)),
+        Pattern::Struct { name, fields } => {
+            let parts = fields.iter().map(|(k, p)| match p {
+                Pattern::Bind(n) if n == k => k.clone(),
+                _ => format!("{} : {}", k, pattern(p)),
+            }).collect::<Vec<String>>();
+            format!("{} {{ {} }}", name, parts.join(", "))
+        },
+    }
+}
+
+/// the way a literal is written in a script. strings have no escapes, so one containing a quote becomes a raw string
+pub fn literal(v : &Value) -> String {
+    match v {
+        Value::String(s) if s.contains('"') => format!("r#\"{}\"#", s),
+        Value::String(s) => format!("\"{}\"", s),
+        Value::Float(f) => float(f),
+        Value::Nil => String::from("{}"),
+        v => format!("{}", v),
+    }
+}
+
+/// the shortest decimal that reads back as `f`, without an exponent since the lexer has none
+fn float(f : &Float) -> String {
+    let mut text = f.to_string_radix(10, None);
+    for digits in 1..=120 {
+        let t = f.to_string_radix(10, Some(digits));
+        if Float::parse(&t).map(|p| Float::with_val(FLOATING_PRECISION, p) == *f).unwrap_or(false) {
+            text = t;
+            break;
+        }
+    }
+
+    let (sign, text) = match text.strip_prefix('-') {
+        Some(t) => ("-", t),
+        None => ("", text.as_str()),
+    };
+    let (mantissa, exponent) = match text.split_once('e') {
+        Some((m, e)) => (m, e.parse::<i64>().unwrap_or(0)),
+        None => (text, 0),
+    };
+
+    let point = mantissa.find('.').unwrap_or(mantissa.len()) as i64 + exponent;
+    let digits : String = mantissa.chars().filter(|c| *c != '.').collect();
+
+    let (whole, fraction) = if point <= 0 {
+        (String::from("0"), format!("{}{}", "0".repeat(-point as usize), digits))
+    } else if point as usize >= digits.len() {
+        (format!("{}{}", digits, "0".repeat(point as usize - digits.len())), String::new())
+    } else {
+        (String::from(&digits[..point as usize]), String::from(&digits[point as usize..]))
+    };
+
+    let fraction = fraction.trim_end_matches('0');
+    let whole = match whole.trim_start_matches('0') {
+        "" => "0",
+        w => w,
+    };
+
+    format!("{}{}.{}", sign, whole, if fraction.is_empty() { "0" } else { fraction })
+}
+
+fn is_name(s : &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => chars.all(|c| c.is_ascii_alphanumeric() || c == '_'),
+        _ => false,
+    }
+}
+
+#[test]
+fn test_printer() -> () {
+    use super::parser::Syntax;
+
+    let syntax = Syntax::new();
+    let text = r##"fn fib(x) { x <= 2 : x, fib(x-1) + fib(x-2) };
+struct Point { x, y = 0.25 };
+p = Point(1, y = 2);
+l = $[1, .5, "a", r#"say "hi""#];
+l[0] = l[1] * (2 + 3);
+p.x -= p.y;
+s = ${1, 2} | ${3};
+r = match p { Point { x : -2..5, y } if y > 0 => y, [a, ...b] => a, _ => { 2 } };
+while i < 3 : [ i += 1; displn(i); ];
+"##;
+
+    let printed = program(&syntax.parse(text).unwrap());
+    assert_eq!(printed, r##"fn fib(x) {
+    x <= 2 : x,
+    fib(x - 1) + fib(x - 2)
+};
+struct Point { x, y = 0.25 };
+p = Point(1, y = 2);
+l = $[1, 0.5, "a", r#"say "hi""#];
+l[0] = l[1] * (2 + 3);
+p.x -= p.y;
+s = ${1, 2} | ${3};
+r = match p {
+    Point { x : -2..5, y } if y > 0 => y,
+    [a, ...b] => a,
+    _ => 2
+};
+while i < 3 : [
+    i += 1;
+    displn(i);
+];
+"##);
+
+    // printing the parse of the output gives the output again
+    assert_eq!(program(&syntax.parse(&printed).unwrap()), printed);
+}
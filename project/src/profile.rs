@@ -0,0 +1,141 @@
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
+
+use super::error::Frame;
+
+/// calls and time spent in one function
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Stats {
+    pub calls : u64,
+    /// from call to return, calls it makes included. recursive calls are only counted once
+    pub inclusive : Duration,
+    /// inclusive minus the time of the calls it makes
+    pub exclusive : Duration,
+}
+
+/// a call that has not returned yet
+struct Active {
+    name : String,
+    start : Instant,
+    /// inclusive time of the calls it made so far
+    children : Duration,
+}
+
+/// records every script function, builtin and native call of the runs it is set on,
+/// see Runtime::set_profiler
+pub struct Profiler {
+    pub functions : HashMap<String, Stats>,
+    /// call stacks as `main;f;g` to the number of times they were sampled
+    pub samples : BTreeMap<String, u64>,
+    /// evaluation steps between two samples
+    interval : u64,
+    steps : u64,
+    active : Vec<Active>,
+}
+
+impl Profiler {
+    /// samples the call stack every `interval` evaluation steps, 1 samples all of them
+    pub fn new(interval : u64) -> Profiler {
+        Profiler {
+            functions : HashMap::new(),
+            samples : BTreeMap::new(),
+            interval : interval.max(1),
+            steps : 0,
+            active : Vec::new(),
+        }
+    }
+
+    pub fn enter(&mut self, name : &str) -> () {
+        self.active.push(Active { name : name.to_string(), start : Instant::now(), children : Duration::ZERO });
+    }
+
+    pub fn exit(&mut self) -> () {
+        let Some(a) = self.active.pop() else {
+            return;
+        };
+        let elapsed = a.start.elapsed();
+
+        if let Some(caller) = self.active.last_mut() {
+            caller.children += elapsed;
+        }
+
+        let recursive = self.active.iter().any(|b| b.name == a.name);
+        let stats = self.functions.entry(a.name).or_default();
+        stats.calls += 1;
+        stats.exclusive += elapsed.saturating_sub(a.children);
+        if !recursive {
+            stats.inclusive += elapsed;
+        }
+    }
+
+    /// called on every evaluation step with the calls running at that point
+    pub fn tick(&mut self, frames : &[Frame]) -> () {
+        self.steps += 1;
+        if !self.steps.is_multiple_of(self.interval) {
+            return;
+        }
+
+        let mut stack = String::from("main");
+        for f in frames {
+            stack.push(';');
+            stack.push_str(&f.name);
+        }
+        *self.samples.entry(stack).or_insert(0) += 1;
+    }
+
+    /// one line per function, the most exclusive time first
+    pub fn summary(&self) -> String {
+        let mut functions = self.functions.iter().collect::<Vec<(&String, &Stats)>>();
+        functions.sort_by(|a, b| b.1.exclusive.cmp(&a.1.exclusive).then(a.0.cmp(b.0)));
+
+        let width = functions.iter().map(|(n, _)| n.len()).max().unwrap_or(0).max("function".len());
+        let ms = |d : &Duration| d.as_secs_f64() * 1000.0;
+
+        let mut ret = format!("{:<width$} {:>10} {:>14} {:>14}\n", "function", "calls", "inclusive ms", "exclusive ms", width = width);
+        for (name, s) in functions {
+            ret += &format!("{:<width$} {:>10} {:>14.3} {:>14.3}\n", name, s.calls, ms(&s.inclusive), ms(&s.exclusive), width = width);
+        }
+
+        return ret;
+    }
+
+    /// the samples as `main;f;g count` lines, the input of flamegraph.pl and inferno
+    pub fn folded(&self) -> String {
+        self.samples.iter().map(|(stack, n)| format!("{} {}\n", stack, n)).collect()
+    }
+}
+
+#[test]
+fn test_profile() {
+    use super::run::Runtime;
+
+    let mut r = Runtime::new();
+    r.capture_output();
+    r.set_profiler(Profiler::new(1));
+    r.run("fn fib(n) { n < 2 : n, fib(n - 1) + fib(n - 2) }; fn go() [ fib(6); len($[1, 2]); upper(\"a\"); displn(str(dim($[1]))); ]; go();").unwrap();
+
+    let steps = r.environment().steps;
+    let p = r.profiler().unwrap();
+    assert_eq!(p.functions["fib"].calls, 25);
+    assert_eq!(p.functions["go"].calls, 1);
+    assert_eq!(p.functions["upper"].calls, 1);
+    assert_eq!(p.functions["len"].calls, 1);
+    // the builtins handled by Environment itself too
+    assert_eq!(p.functions["displn"].calls, 1);
+    assert_eq!(p.functions["str"].calls, 1);
+    assert_eq!(p.functions["dim"].calls, 1);
+    assert!(p.functions["go"].inclusive >= p.functions["fib"].inclusive);
+    assert!(p.functions["go"].exclusive <= p.functions["go"].inclusive);
+
+    // every step was sampled, under go while it ran
+    assert_eq!(p.samples.values().sum::<u64>(), steps);
+    let folded = p.folded();
+    assert!(folded.lines().any(|l| l.starts_with("main;go;fib;fib;fib ")));
+    assert!(folded.lines().any(|l| l.starts_with("main;go ")));
+    assert!(folded.lines().all(|l| l.starts_with("main") && l.rsplit(' ').next().unwrap().parse::<u64>().is_ok()));
+
+    let summary = p.summary();
+    assert!(summary.starts_with("function "));
+    assert_eq!(summary.lines().count(), 8);
+    assert!(summary.lines().any(|l| l.starts_with("fib ") && l.contains(" 25 ")));
+}
@@ -0,0 +1,299 @@
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::{ValidationContext, ValidationResult, Validator};
+use rustyline::{Context, Editor, Helper};
+
+use project::environment::BUILTINS;
+use project::lexer::KEYWORDS;
+use project::{builtins, ErrorKind, Runtime, Value};
+
+const HELP : &str = "\
+Enter expressions, a missing trailing ';' is added. Unclosed brackets continue on the next line.
+Tab completes builtins and defined names, Ctrl+C clears the input and Ctrl+D exits.
+
+    :help          this message
+    :vars          global variables
+    :funcs         functions and structs
+    :reset         forget every definition
+    :load FILE     run a script in this session
+    :time EXPR     evaluate and report how long it took
+    :ast EXPR      show the parsed expression
+    :quit          exit, so does calling exit(status)";
+
+/// completion and continuation for the line editor
+struct Session {
+    /// builtins, keywords and everything defined so far
+    names : Vec<String>,
+}
+
+impl Completer for Session {
+    type Candidate = String;
+
+    fn complete(&self, line : &str, pos : usize, _ : &Context<'_>) -> rustyline::Result<(usize, Vec<String>)> {
+        let start = line[..pos].rfind(|c : char| !(c.is_alphanumeric() || c == '_' || c == '.' || c == ':')).map(|i| i + 1).unwrap_or(0);
+        let word = &line[start..pos];
+
+        if word.is_empty() {
+            return Ok((pos, vec![]));
+        }
+
+        let mut found : Vec<String> = if word.starts_with(':') {
+            HELP.lines().filter_map(|l| l.split_whitespace().next()).filter(|c| c.starts_with(word)).map(String::from).collect()
+        } else {
+            self.names.iter().filter(|n| n.starts_with(word)).cloned().collect()
+        };
+
+        found.sort();
+        found.dedup();
+        Ok((start, found))
+    }
+}
+
+impl Hinter for Session {
+    type Hint = String;
+}
+
+impl Highlighter for Session {}
+
+impl Validator for Session {
+    fn validate(&self, ctx : &mut ValidationContext) -> rustyline::Result<ValidationResult> {
+        let input = ctx.input();
+
+        if !input.trim_start().starts_with(':') && unclosed(input) {
+            return Ok(ValidationResult::Incomplete);
+        }
+
+        Ok(ValidationResult::Valid(None))
+    }
+}
+
+impl Helper for Session {}
+
+/// whether text has more opening than closing brackets, ignoring strings and comments
+pub fn unclosed(text : &str) -> bool {
+    let mut depth : i64 = 0;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                for c in chars.by_ref() {
+                    if c == '"' {
+                        break;
+                    }
+                }
+            },
+            '/' if chars.peek() == Some(&'/') => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            },
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth -= 1,
+            _ => {},
+        }
+    }
+
+    depth > 0
+}
+
+fn history_file() -> Option<PathBuf> {
+    let home = std::env::var_os("HOME")?;
+    Some(Path::new(&home).join(".project_history"))
+}
+
+/// the names tab completion offers
+fn names(r : &mut Runtime) -> Vec<String> {
+    let env = r.environment();
+    let mut names : Vec<String> = KEYWORDS.iter().chain(BUILTINS.iter()).map(|s| String::from(*s)).collect();
+
+    names.extend(builtins::names().map(String::from));
+    names.extend(env.natives.keys().cloned());
+    names.extend(env.functions.keys().cloned());
+    names.extend(env.structs.keys().cloned());
+    names.extend(env.envs[0].keys().cloned());
+
+    names
+}
+
+/// runs the interactive shell until :quit or end of input
+pub fn start() -> () {
+    let mut editor : Editor<Session, DefaultHistory> = match Editor::new() {
+        Ok(e) => e,
+        Err(e) => {
+            eprintln!("could not start the line editor : {}", e);
+            return;
+        },
+    };
+
+    let mut r = Runtime::new();
+    editor.set_helper(Some(Session { names : names(&mut r) }));
+
+    let history = history_file();
+    if let Some(h) = &history {
+        // there is no history yet on the first run
+        let _ = editor.load_history(h);
+    }
+
+    println!("Shell Mode! :help for help, :quit or Ctrl+D to exit");
+
+    let mut status = 0;
+
+    loop {
+        let line = match editor.readline("> ") {
+            Ok(l) => l,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => {
+                eprintln!("{}", e);
+                break;
+            },
+        };
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let _ = editor.add_history_entry(line.as_str());
+
+        if let Some(code) = command(&mut r, line.trim()) {
+            status = code;
+            break;
+        }
+
+        if let Some(s) = editor.helper_mut() {
+            s.names = names(&mut r);
+        }
+    }
+
+    if let Some(h) = &history {
+        if let Err(e) = editor.save_history(h) {
+            eprintln!("could not save the history to {} : {}", h.display(), e);
+        }
+    }
+
+    if status != 0 {
+        std::process::exit(status);
+    }
+}
+
+/// the grammar wants every expression terminated
+fn terminated(code : &str) -> String {
+    let code = code.trim_end();
+
+    if code.ends_with(';') {
+        String::from(code)
+    } else {
+        format!("{};", code)
+    }
+}
+
+/// Some(status) when the code called exit
+fn eval(r : &mut Runtime, code : &str) -> Option<i32> {
+    match r.run(&terminated(code)) {
+        Ok(Value::Nil) => {},
+        Ok(v) => println!("{}", v),
+        Err(e) if e.kind == ErrorKind::Exit => return Some(e.exit_code()),
+        Err(e) => eprintln!("{}", e),
+    }
+
+    None
+}
+
+/// handles one input, Some(status) to exit
+fn command(r : &mut Runtime, line : &str) -> Option<i32> {
+    if !line.starts_with(':') {
+        return eval(r, line);
+    }
+
+    let (cmd, rest) = match line.split_once(char::is_whitespace) {
+        Some((c, rest)) => (c, rest.trim()),
+        None => (line, ""),
+    };
+
+    match cmd {
+        ":help" => println!("{}", HELP),
+        ":quit" | ":q" => return Some(0),
+        ":reset" => {
+            *r = Runtime::new();
+            println!("every definition was forgotten");
+        },
+        ":vars" => {
+            let mut vars : Vec<(&String, &Value)> = r.environment().envs[0].iter().collect();
+            vars.sort_by(|a, b| a.0.cmp(b.0));
+
+            for (name, v) in vars {
+                println!("{} = {}", name, v);
+            }
+        },
+        ":funcs" => {
+            let env = r.environment();
+            let mut lines = Vec::new();
+
+            for (name, f) in env.functions.iter() {
+                lines.push(format!("fn {}({})", name, f.params.iter().map(|p| format!("{}", p)).collect::<Vec<String>>().join(", ")));
+            }
+            for (name, s) in env.structs.iter() {
+                lines.push(format!("struct {} {{ {} }}", name, s.fields.iter().map(|p| format!("{}", p)).collect::<Vec<String>>().join(", ")));
+            }
+
+            lines.sort();
+            for l in lines {
+                println!("{}", l);
+            }
+        },
+        ":load" => {
+            if rest.is_empty() {
+                eprintln!(":load needs a file");
+                return None;
+            }
+
+            let path = Path::new(rest);
+            match std::fs::read_to_string(path) {
+                Ok(text) => match r.run_script(&text, path) {
+                    Ok(_) => {},
+                    Err(e) if e.kind == ErrorKind::Exit => return Some(e.exit_code()),
+                    Err(e) => eprintln!("{}", e),
+                },
+                Err(e) => eprintln!("could not read {} : {}", rest, e),
+            }
+        },
+        ":time" => {
+            let start = Instant::now();
+            let status = eval(r, rest);
+            println!("took {:?}", start.elapsed());
+            return status;
+        },
+        ":ast" => {
+            match r.environment().syntax.parse(&terminated(rest)) {
+                Ok(v) => {
+                    for a in v {
+                        println!("{:#?}", a);
+                    }
+                },
+                Err(e) => eprintln!("{}", e),
+            }
+        },
+        _ => eprintln!("unknown command {}, :help lists them", cmd),
+    }
+
+    None
+}
+
+#[test]
+fn test_unclosed() -> () {
+    assert!(unclosed("fn f(x) {"));
+    assert!(unclosed("$[1, $[2]"));
+    assert!(!unclosed("f(\"(\")"));
+    assert!(!unclosed("x = 1; // ("));
+    assert!(!unclosed("[a;]"));
+    assert_eq!(terminated("x = 1  "), "x = 1;");
+}
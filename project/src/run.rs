@@ -1,48 +1,163 @@
 #![allow(unused)]
 
-use super::parser::{grammar, PR};
-use super::lexer::rules;
-use super::environment::Environment;
-use super::error::error;
+use super::environment::{Environment, NativeFunction};
 use super::value::Value;
+use super::output::SharedBuffer;
+use super::error::{RuntimeError, ErrorKind};
+use super::limits::Limits;
+use super::ast::{Span, Vast};
+use super::debug::Debugger;
+use super::profile::Profiler;
 
-use santiago::grammar::Grammar;
-use santiago::lexer::LexerRules;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 
 pub struct Runtime {
-    parser : Grammar<PR>,
-    lexer : LexerRules,
     environment : Environment,
+    captured : Option<SharedBuffer>,
+}
+
+impl Default for Runtime {
+    fn default() -> Runtime {
+        Runtime::new()
+    }
 }
 
 impl Runtime {
     pub fn new() -> Runtime {
         Runtime {
-            parser: grammar(),
-            lexer: rules(),
             environment: Environment::new(),
+            captured: None,
         }
     }
 
-    pub fn run(&mut self, text : &str) -> Value {
+    /// Err is a syntax error (ErrorKind::Syntax) or an error raised while running
+    pub fn run(&mut self, text : &str) -> Result<Value, RuntimeError> {
+        match self.parse(text) {
+            Ok(tree) => self.execute(&tree),
+            Err(e) => Err(RuntimeError::syntax(e, self.environment.syntax.error_at(text))),
+        }
+    }
 
-        let Ok(lexemes) = santiago::lexer::lex(&self.lexer, &text) else {
-            return error(format!("Invalid Syntax!"));
-        };
+    /// like run, imports in `text` are resolved relative to the directory of `path`
+    pub fn run_script(&mut self, text : &str, path : &Path) -> Result<Value, RuntimeError> {
+        let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
 
-        let Ok( trees ) = santiago::parser::parse(&self.parser, &lexemes) else {
-            return error(format!("Invalid Structure of Program!"));
-        };
+        self.environment.loading.push(path);
+        let ret = self.run(text);
+        self.environment.loading.pop();
 
-        let mut tree = trees[0].as_abstract_syntax_tree();
+        ret
+    }
 
+    /// the program in `text` without running it, Err is the syntax error
+    pub fn parse(&self, text : &str) -> Result<Vast, String> {
+        self.environment.syntax.parse(text)
+    }
+
+    /// runs a parsed program, the value is the one of its last expression
+    pub fn execute(&mut self, tree : &Vast) -> Result<Value, RuntimeError> {
         let mut v = Value::Nil;
 
-        for i in tree.force_vast() {
-            v = i.eval(&mut self.environment);
+        self.environment.reset_counters();
+
+        for i in tree {
+            v = i.eval(&mut self.environment)?;
         }
 
-        return v;
+        return Ok(v);
+    }
+
+    /// directories searched by import after the one of the importing file
+    pub fn add_search_path<P : Into<PathBuf>>(&mut self, dir : P) -> () {
+        self.environment.search_paths.push(dir.into());
+    }
+
+    /// the command line arguments after the script
+    pub fn set_args(&mut self, args : Vec<String>) -> () {
+        self.environment.args = args;
+    }
+
+    /// applies to every following run and call
+    pub fn set_limits(&mut self, limits : Limits) -> () {
+        self.environment.limits = limits;
+    }
+
+    pub fn limits(&self) -> &Limits {
+        &self.environment.limits
+    }
+
+    /// makes `f` callable from scripts as `name(...)`. \
+    /// builtins take precedence over natives, natives over script functions
+    pub fn register<F>(&mut self, name : &str, f : F) -> ()
+    where F : Fn(Vec<Value>) -> Value + 'static {
+        let f : NativeFunction = Rc::new(f);
+        self.environment.push_native(&String::from(name), f);
+    }
+
+    pub fn set_global<T : Into<Value>>(&mut self, name : &str, value : T) -> () {
+        self.environment.envs[0].insert(String::from(name), value.into());
+    }
+
+    /// None if the global is unset or does not convert to T
+    pub fn get_global<T : TryFrom<Value>>(&self, name : &str) -> Option<T> {
+        let v = self.environment.envs[0].get(name)?.clone();
+        T::try_from(v).ok()
+    }
+
+    /// calls a builtin, native or script defined function with arguments in call order
+    pub fn call(&mut self, name : &str, mut args : Vec<Value>) -> Result<Value, RuntimeError> {
+        args.reverse(); // eval_function expects the grammar's argument order
+        self.environment.reset_counters();
+        self.environment.eval_function(&String::from(name), args, vec![], Span::default())
+    }
+
+    /// replaces the sink of disp and displn
+    pub fn set_output(&mut self, out : Box<dyn Write>) -> () {
+        self.captured = None;
+        self.environment.set_output(out);
+    }
+
+    /// replaces the sink errors are reported on
+    pub fn set_diagnostics(&mut self, out : Box<dyn Write>) -> () {
+        self.environment.set_diagnostics(out);
+    }
+
+    /// from now on disp and displn write into a buffer, see take_output
+    pub fn capture_output(&mut self) -> () {
+        if self.captured.is_none() {
+            let buf = SharedBuffer::new();
+            self.environment.set_output(Box::new(buf.clone()));
+            self.captured = Some(buf);
+        }
+    }
+
+    /// returns everything captured so far and clears the buffer
+    pub fn take_output(&mut self) -> String {
+        match &self.captured {
+            Some(buf) => buf.take(),
+            None => String::new(),
+        }
+    }
+
+    /// pauses the following runs at breakpoints and steps, see debug::Debugger
+    pub fn set_debugger(&mut self, debugger : Debugger) -> () {
+        self.environment.debugger = Some(Box::new(debugger));
+    }
+
+    /// records calls and call stacks of the following runs, see profile::Profiler
+    pub fn set_profiler(&mut self, profiler : Profiler) -> () {
+        self.environment.profiler = Some(Box::new(profiler));
+    }
+
+    /// what was recorded since set_profiler
+    pub fn profiler(&self) -> Option<&Profiler> {
+        self.environment.profiler.as_deref()
+    }
+
+    pub fn environment(&mut self) -> &mut Environment {
+        &mut self.environment
     }
 }
 
@@ -94,5 +209,522 @@ displn(c);
 displn(1/0.000000);
 
 "###
-    );
-}
\ No newline at end of file
+    ).unwrap_err();
+}
+
+#[test]
+fn test_embedding() -> () {
+    let mut r = Runtime::new();
+    r.capture_output();
+
+    r.register("twice", |args : Vec<Value>| {
+        let n : i64 = args[0].clone().try_into().unwrap_or(0);
+        Value::from(n * 2)
+    });
+    r.set_global("base", 20);
+
+    r.run("fn add(a, b) { a + b }; x = twice(base) + 2; displn(x);").unwrap();
+
+    assert_eq!(r.take_output(), "42\n");
+    assert_eq!(r.get_global::<i64>("x"), Some(42));
+    assert_eq!(r.get_global::<String>("x"), None);
+
+    let sum : i64 = r.call("add", vec![Value::from(1), Value::from(2)]).unwrap().try_into().unwrap();
+    assert_eq!(sum, 3);
+}
+
+#[test]
+fn test_diagnostics() -> () {
+    let mut r = Runtime::new();
+    let errors = SharedBuffer::new();
+
+    r.capture_output();
+    r.set_diagnostics(Box::new(errors.clone()));
+
+    r.run("disp(1);").unwrap();
+    r.run("disp(nope);").unwrap_err();
+
+    // a syntax error is returned to the host, nothing runs and nothing is reported
+    let e = r.run("disp(2);\ndisp(1 +;").unwrap_err();
+    assert_eq!(e.kind, ErrorKind::Syntax);
+    assert_eq!(e.at, Some(Span { line : 2, column : 9 }));
+    assert_eq!(format!("{}", e), "syntax: Invalid Structure of Program! at 2:9");
+
+    assert_eq!(r.take_output(), "1");
+    assert!(errors.contents().is_empty());
+}
+
+#[test]
+fn test_limits() -> () {
+    use super::error::ErrorKind;
+    use std::time::Duration;
+
+    let mut r = Runtime::new();
+
+    r.set_limits(Limits::new().steps(10_000));
+    assert_eq!(r.run("while 1 : [];").unwrap_err().kind, ErrorKind::StepLimit);
+
+    r.set_limits(Limits::new().depth(50));
+    assert_eq!(r.run("fn f(x) f(x + 1); f(0);").unwrap_err().kind, ErrorKind::StackOverflow);
+    assert_eq!(r.run("f(0);").unwrap_err().kind, ErrorKind::StackOverflow); // the depth is restored after an error
+    assert!(r.run("fn g(x) { x <= 0 : 0, g(x - 1) }; g(40);").is_ok());
+
+    r.set_limits(Limits::new().size(100));
+    assert_eq!(r.run("a = $[1]; while 1 : [a = a + a;];").unwrap_err().kind, ErrorKind::MemoryLimit);
+    assert_eq!(r.run("s = \"ab\"; while 1 : [s = s + s;];").unwrap_err().kind, ErrorKind::MemoryLimit);
+
+    r.set_limits(Limits::new().timeout(Duration::from_millis(50)));
+    assert_eq!(r.run("while 1 : [];").unwrap_err().kind, ErrorKind::Timeout);
+}
+
+#[test]
+fn test_stack_trace() -> () {
+    let mut r = Runtime::new();
+
+    let e = r.run("fn down(x) { x <= 0 : 1 / 0, down(x - 1) };\ndown(3);").unwrap_err();
+    let shown = format!("{}", e);
+    assert!(shown.contains("down(x=0) called at 1:30"));
+    assert!(shown.contains("down ... (repeated 3 times)"));
+
+    r.set_limits(Limits::new().depth(100));
+    let e = r.run("fn f(x) f(x + 1);\nf(0);").unwrap_err();
+
+    assert_eq!(e.trace.len(), 100);
+    assert!(format!("{}", e).contains("f ... (repeated 99 times)"));
+}
+
+#[test]
+fn test_try_catch() -> () {
+    let mut r = Runtime::new();
+    r.capture_output();
+
+    r.run(r#"
+fn safe_div(a, b) try a / b catch (e) [displn(e.kind); displn(e.message); 0;];
+displn(safe_div(6, 3));
+displn(safe_div(1, 0));
+
+fn check(x) { x < 0 : throw "negative", x };
+try check(0 - 5) catch (e) [displn(e.kind, e.value); displn(e.trace);];
+
+try get($[1], 3) catch (e) displn(e.kind);
+try (try 1 + nil_var catch (e) throw e) catch (e) displn(e.kind);
+"#).unwrap();
+
+    assert_eq!(r.take_output(), "2\ndivision by zero\ndivision by zero!\n0\nthrown\nnegative\n[check(x=-5) called at 7:5,]\nindex\nname\n");
+
+    // the handler and the thrown value extend to the right like an assignment
+    r.run(r#"
+x = try 5 catch (e) 1 + 1;
+y = try throw "a" + "b" catch (e) e.value + "c";
+z = try throw 1 catch (e) try throw 2 catch (f) f.value * 10;
+"#).unwrap();
+    assert_eq!(r.get_global::<i64>("x"), Some(5));
+    assert_eq!(r.get_global::<String>("y"), Some(String::from("abc")));
+    assert_eq!(r.get_global::<i64>("z"), Some(20));
+
+    // values without arithmetic are a type error, not nil
+    r.run("struct P { x, y };").unwrap();
+    for src in ["P(1, 2) + 1;", "${1} * ${2};", "upper + 1;", "1 - P(1, 2);", "(try throw 1 catch (e) e) / 2;", "upper % upper;"] {
+        assert_eq!(r.run(src).unwrap_err().kind, ErrorKind::Type, "{}", src);
+    }
+    r.run("k = try P(1, 2) + 1 catch (e) e.kind;").unwrap();
+    assert_eq!(r.get_global::<String>("k"), Some(String::from("type")));
+    r.run("d = ${1, 2} - ${2};").unwrap();
+
+    // the catch variable does not outlive the handler
+    r.run("e = 5; try throw 1 catch (e) 0; fn g() [ e2 = 6; try throw 1 catch (e2) 0; e2; ]; h = g();").unwrap();
+    assert_eq!(r.get_global::<i64>("e"), Some(5));
+    assert_eq!(r.get_global::<i64>("h"), Some(6));
+
+    // limits are not catchable
+    r.set_limits(Limits::new().steps(1000));
+    assert!(r.run("try [while 1 : [];] catch (e) 0;").is_err());
+}
+
+#[test]
+fn test_parameters() -> () {
+    let mut r = Runtime::new();
+    r.capture_output();
+
+    r.run(r#"
+fn greet(name, greeting = "hello", punct = greeting) { displn(greeting, name, punct) };
+greet("a");
+greet("b", "hi");
+greet("c", punct = "!");
+
+fn count(first, ...rest) { displn(first, dim(rest), rest) };
+count(1);
+count(1, 2, 3);
+"#).unwrap();
+
+    assert_eq!(r.take_output(), "hello\na\nhello\nhi\nb\nhi\nhello\nc\n!\n1\n0\n[]\n1\n2\n[2,3,]\n");
+
+    for (src, kind) in [
+        ("fn f(a, b) { a }; f(1);", ErrorKind::Arity),
+        ("fn f(a, b) { a }; f(1, 2, 3);", ErrorKind::Arity),
+        ("fn f(a, b) { a }; f(1, a = 2);", ErrorKind::Arity),
+        ("fn f(a, b) { a }; f(1, c = 2);", ErrorKind::Arity),
+        ("fn f(...a, b) { a };", ErrorKind::Arity),
+        ("displn(x = 1);", ErrorKind::Arity),
+    ] {
+        assert_eq!(r.run(src).unwrap_err().kind, kind, "{}", src);
+    }
+
+    assert_eq!(r.get_global::<i64>("x"), None);
+}
+
+#[test]
+fn test_structs() -> () {
+    let mut r = Runtime::new();
+    r.capture_output();
+
+    r.run(r#"
+struct Point { x, y = x };
+struct Line { from, to };
+p = Point(1, 2);
+displn(p, p.x, type_of(p), type_of(1));
+p.x = 5;
+displn(Point(3), Point(y = 1, x = 0));
+
+l = Line(p, Point(0, 0));
+l.to.y = 7;
+displn(l.to, l.from.x, dim(l));
+"#).unwrap();
+
+    assert_eq!(r.take_output(), "Point { x : 1, y : 2 }\n1\nPoint\nint\nPoint { x : 3, y : 3 }\nPoint { x : 0, y : 1 }\nPoint { x : 0, y : 7 }\n5\n2\n");
+
+    for (src, kind) in [
+        ("Point();", ErrorKind::Arity),
+        ("Point(1, z = 2);", ErrorKind::Arity),
+        ("p.z;", ErrorKind::Name),
+        ("p.z = 1;", ErrorKind::Name),
+        ("q = 1; q.x = 2;", ErrorKind::Type),
+    ] {
+        assert_eq!(r.run(src).unwrap_err().kind, kind, "{}", src);
+    }
+}
+
+#[test]
+fn test_match() -> () {
+    let mut r = Runtime::new();
+    r.capture_output();
+
+    r.run(r#"
+fn describe(v) match v {
+    0 => "zero",
+    -1 => "minus one",
+    "hi" => "greeting",
+    1..10 => "small",
+    [] => "empty",
+    [x] => "one " + str(x),
+    [head, ..._] if head > 100 => "big head",
+    [head, ...tail] => "head " + str(head) + " tail " + str(dim(tail)),
+    n if n > 100 => "big",
+    _ => "other",
+};
+
+displn(describe(0), describe(0 - 1), describe("hi"), describe(5), describe(10), describe(500));
+displn(describe($[]), describe($[7]), describe($[1, 2, 3]), describe($[200, 1]));
+"#).unwrap();
+
+    assert_eq!(r.take_output(), "zero\nminus one\ngreeting\nsmall\nother\nbig\nempty\none 7\nhead 1 tail 2\nbig head\n");
+
+    // bindings of an arm do not outlive it, and a failed guard leaves the variables it shadowed alone
+    r.run(r#"
+x = 5;
+a = match $[1] { [x] if x > 3 => 1, _ => 0 };
+b = match 7 { n => n + x };
+fn f() [ y = 6; match $[1] { [y] if y > 3 => 1, _ => 0 }; y; ];
+c = f();
+"#).unwrap();
+    assert_eq!(r.get_global::<i64>("x"), Some(5));
+    assert_eq!(r.get_global::<i64>("a"), Some(0));
+    assert_eq!(r.get_global::<i64>("b"), Some(12));
+    assert_eq!(r.get_global::<i64>("n"), None);
+    assert_eq!(r.get_global::<i64>("c"), Some(6));
+
+    let e = r.run("match 3 { 1 => 1, [a] => a };").unwrap_err();
+    assert_eq!(e.kind, ErrorKind::Match);
+    assert_eq!(e.message, "no pattern matched 3");
+}
+
+#[test]
+fn test_destructuring() -> () {
+    let mut r = Runtime::new();
+    r.capture_output();
+
+    r.run(r#"
+fn min_max(a, b) { a < b : $[a, b], $[b, a] };
+[lo, hi] = min_max(9, 4);
+[first, ...rest] = $[1, 2, 3];
+displn(lo, hi, first, rest);
+
+struct Point { x, y };
+Point { x, y : py } = Point(3, 4);
+displn(x, py);
+displn(match Point(0, 5) { Point { x : 0, y } => y, _ => 0 });
+"#).unwrap();
+
+    assert_eq!(r.take_output(), "4\n9\n1\n[2,3,]\n3\n4\n5\n");
+
+    let e = r.run("[a, b] = $[1, 2, 3];").unwrap_err();
+    assert_eq!(e.kind, ErrorKind::Match);
+    assert_eq!(e.message, "cannot destructure [1,2,3,] into [a, b]");
+    assert_eq!(r.run("Point { x } = 1;").unwrap_err().kind, ErrorKind::Match);
+}
+
+#[test]
+fn test_compound_assignment() -> () {
+    let mut r = Runtime::new();
+    r.capture_output();
+
+    r.run(r#"
+c = 0;
+while c < 5 : [c += 1;];
+x = 10; x -= 3; x *= 2; x /= 7; x ^= 3; x %= 5;
+displn(c, x);
+
+xs = $[1, 2, $[3, 4]];
+xs[0] += 10;
+xs[2][1] *= 5;
+xs[1] = "two";
+displn(xs, xs[2][0], "abc"[1]);
+
+struct Counter { n };
+k = Counter(1);
+k.n += 41;
+displn(k.n);
+"#).unwrap();
+
+    assert_eq!(r.take_output(), "5\n3\n[11,two,[3,20,],]\n3\nb\n42\n");
+
+    // every index of a nested place is evaluated once
+    r.run(r#"
+calls = 0;
+fn g() [ calls += 1; 2; ];
+xs[g()][1] += 5;
+xs[g()][0] = 7;
+ps = $[Counter(1)];
+ps[calls - 2].n += 1;
+displn(xs[2], ps[0].n, calls);
+"#).unwrap();
+    assert_eq!(r.take_output(), "[7,25,]\n2\n2\n");
+
+    for (src, kind) in [
+        ("xs[3] += 1;", ErrorKind::Index),
+        ("xs[\"a\"] = 1;", ErrorKind::Type),
+        ("missing += 1;", ErrorKind::Name),
+        ("1 += 1;", ErrorKind::Type),
+    ] {
+        assert_eq!(r.run(src).unwrap_err().kind, kind, "{}", src);
+    }
+}
+
+#[test]
+fn test_equality() -> () {
+    let mut r = Runtime::new();
+    r.capture_output();
+
+    r.run(r#"
+struct P { x };
+displn("a" == "a", $[1, $[2]] == $[1, $[2.0]], P(1) == P(1), P(1) != P(2), 1 == "1");
+displn("apple" < "banana", $[1, 2] < $[1, 3], $[] < $[0]);
+"#).unwrap();
+
+    assert_eq!(r.take_output(), "true\ntrue\ntrue\ntrue\nfalse\ntrue\ntrue\ntrue\n");
+    assert_eq!(r.run("1 < \"a\";").unwrap_err().kind, ErrorKind::Type);
+}
+
+#[test]
+fn test_sets() -> () {
+    let mut r = Runtime::new();
+    r.capture_output();
+
+    r.run(r#"
+a = ${3, 1, 2, 1, 1.0};
+b = set($[2, 3, 4, $[1], $[1]]);
+displn(a, dim(b), type_of(a));
+displn(a | b, a & b, a - b, b - a);
+displn(2 in a, 5 in a, $[1] in b, "ell" in "hello", 3 in $[1, 2, 3]);
+displn(a == ${1, 2, 3}, ${1} < a, vec(a), int(2.0) in a);
+"#).unwrap();
+
+    assert_eq!(r.take_output(), "${3,1,2,}\n4\nset\n${3,1,2,4,[1,],}\n${3,2,}\n${1,}\n${4,[1,],}\ntrue\nfalse\ntrue\ntrue\ntrue\ntrue\ntrue\n[3,1,2,]\ntrue\n");
+
+    assert_eq!(r.run("${1} | $[1];").unwrap_err().kind, ErrorKind::Type);
+    assert_eq!(r.run("1 in 2;").unwrap_err().kind, ErrorKind::Type);
+}
+
+#[test]
+fn test_list_builtins() -> () {
+    let mut r = Runtime::new();
+    r.capture_output();
+
+    r.run(r#"
+fn double(x) { x * 2 };
+fn even(x) { x % 2 == 0 };
+fn add(a, b) { a + b };
+fn desc(a, b) { b - a };
+xs = $[3, 1, 2];
+
+displn(map(xs, double), filter(xs, even), reduce(xs, add), fold(xs, 10, add));
+displn(sort(xs), sort(xs, desc), sort_by_key($["ccc", "a", "bb"], dim), reverse(xs));
+displn(push(xs, 4), insert(xs, 0, 0), remove_at(xs, 1), concat(xs, $[9], $[]));
+displn(any(xs, even), all(xs, even), find(xs, even), index_of(xs, 2), index_of(xs, 7));
+displn(zip(xs, $["a", "b"]), enumerate($["x"]), flatten($[$[1, 2], 3, $[]]));
+displn(chunks($[1, 2, 3, 4, 5], 2), windows($[1, 2, 3], 2), unique($[1, 2, 1, 3, 2]));
+displn(sum($[1, 2, 3]), product($[2, 3, 4]), sum($[1, 0.5]) == 1.5, sum($[]), product($[2 ^ 64, 2 ^ 64]));
+
+f = double;
+displn(f(21), type_of(f));
+"#).unwrap();
+
+    assert_eq!(r.take_output(), "[6,2,4,]\n[2,]\n6\n16\n\
+[1,2,3,]\n[3,2,1,]\n[a,bb,ccc,]\n[2,1,3,]\n\
+[3,1,2,4,]\n[0,3,1,2,]\n[3,2,]\n[3,1,2,9,]\n\
+true\nfalse\n2\n2\nnil\n\
+[[3,a,],[1,b,],]\n[[0,x,],]\n[1,2,3,]\n\
+[[1,2,],[3,4,],[5,],]\n[[1,2,],[2,3,],]\n[1,2,3,]\n\
+6\n24\ntrue\n0\n340282366920938463463374607431768211456\n\
+42\nfunction\n");
+
+    for (src, kind) in [
+        ("map(1, double);", ErrorKind::Type),
+        ("map(xs);", ErrorKind::Arity),
+        ("reduce($[], add);", ErrorKind::Index),
+        ("sort($[1, \"a\"]);", ErrorKind::Type),
+        ("chunks(xs, 0);", ErrorKind::Type),
+        ("sum($[\"a\"]);", ErrorKind::Type),
+        ("map(xs, 5);", ErrorKind::Type),
+        ("map(xs, x = 1);", ErrorKind::Arity),
+        // calls would never reach a function or struct named like a builtin
+        ("fn sum(a, b) { a + b };", ErrorKind::Name),
+        ("fn upper(s) { s };", ErrorKind::Name),
+        ("struct len { x };", ErrorKind::Name),
+        ("fn displn(x) { x };", ErrorKind::Name),
+    ] {
+        assert_eq!(r.run(src).unwrap_err().kind, kind, "{}", src);
+    }
+
+    // a comparator that is not a total order gives some order of the same elements
+    r.run(r#"
+s = 1;
+fn c(a, b) [ s = (s * 1103515245 + 12345) % 2147483648; s % 3 - 1; ];
+ys = $[];
+while dim(ys) < 50 : [ys = push(ys, dim(ys) % 7);];
+zs = sort(ys, c);
+"#).unwrap();
+    r.run("n = dim(zs); same = sort(zs) == sort(ys);").unwrap();
+    assert_eq!(r.get_global::<i64>("n"), Some(50));
+    assert_eq!(r.get_global::<bool>("same"), Some(true));
+
+    // the size limit is checked before the result is built
+    r.run("big = $[]; while dim(big) < 60 : [big = push(big, 0);];").unwrap();
+    r.set_limits(Limits::new().size(100));
+    for src in ["concat(big, big);", "flatten($[big, big]);"] {
+        assert_eq!(r.run(src).unwrap_err().kind, ErrorKind::MemoryLimit, "{}", src);
+    }
+    assert!(r.run("concat(big, $[1]); flatten($[big, 1]); chunks(big, 1); windows(big, 1);").is_ok());
+}
+
+#[test]
+fn test_string_builtins() -> () {
+    let mut r = Runtime::new();
+    r.capture_output();
+
+    r.run(r#"
+s = "héllo wörld";
+displn(dim(s), len(s), upper(s), lower("ABC"), trim("  x "), trim_start(" x"), trim_end("x "));
+displn(split("a,b,,c", ","), split(" a  b "), join($["a", 1, "c"], "-"), replace("aXbX", "X", "y"));
+displn(contains(s, "wö"), starts_with(s, "hé"), ends_with(s, "x"), find(s, "wö"), find(s, "z"));
+displn(repeat("ab", 3), pad_left("7", 3, "0"), pad_right("ab", 4) + "|", chars("hé"), reverse("abc"));
+displn(ord("é"), chr(246), parse_int(" 42 ") + 1, parse_float("2.5") * 2 == 5);
+displn(try parse_int("4x2") catch (e) e.kind);
+"#).unwrap();
+
+    assert_eq!(r.take_output(), "11\n11\nHÉLLO WÖRLD\nabc\nx\nx\nx\n\
+[a,b,,c,]\n[a,b,]\na-1-c\nayby\n\
+true\ntrue\nfalse\n6\nnil\n\
+ababab\n007\nab  |\n[h,é,]\ncba\n\
+233\nö\n43\ntrue\nparse\n");
+
+    // e followed by a combining acute accent
+    r.set_global("e", "cafe\u{301}");
+    r.run("displn(len(e), grapheme_len(e), graphemes(e), reverse(e));").unwrap();
+    assert_eq!(r.take_output(), "5\n4\n[c,a,f,e\u{301},]\ne\u{301}fac\n");
+
+    let e = r.run("parse_float(\"abc\");").unwrap_err();
+    assert_eq!((e.kind, e.message), (ErrorKind::Parse, String::from("cannot parse \"abc\" as a float")));
+
+    // the size limit is checked before a huge string is built
+    r.set_limits(Limits::new().size(100));
+    for src in ["repeat(\"ab\", 100000000000);", "pad_left(\"a\", 100000000000);", "pad_right(\"a\", 101, \"é\");"] {
+        assert_eq!(r.run(src).unwrap_err().kind, ErrorKind::MemoryLimit, "{}", src);
+    }
+    assert!(r.run("pad_left(\"a\", 100);").is_ok());
+}
+
+#[test]
+fn test_regex_builtins() -> () {
+    let mut r = Runtime::new();
+    r.capture_output();
+
+    r.run(r##"
+date = r"(\d{4})-(\d{2})-(\d{2})";
+displn(re_match(date, "on 2024-01-31."), re_match(date, "never"), re_match(r"(a)|(b)", "b"));
+displn(re_find_all(r"\w+", "one, two  three"), re_captures(r"(\w)(\d)", "a1 b2"));
+displn(re_replace(date, "2024-01-31", "$3/$2/$1"), re_split(r"\s*,\s*", "a , b,c"));
+"##).unwrap();
+
+    r.set_global("quoted", "they say \"hi\"");
+    r.run(r##"displn(re_match(r#"say "(\w+)""#, quoted));"##).unwrap();
+
+    assert_eq!(r.take_output(), "[2024-01-31,2024,01,31,]\nnil\n[b,nil,b,]\n\
+[one,two,three,]\n[[a1,a,1,],[b2,b,2,],]\n\
+31/01/2024\n[a,b,c,]\n\
+[say \"hi\",hi,]\n");
+
+    let e = r.run("re_match(r\"(\", \"x\");").unwrap_err();
+    assert_eq!(e.kind, ErrorKind::Parse);
+    assert_eq!(r.environment().regexes.len(), 6);
+}
+
+#[test]
+fn test_system_builtins() -> () {
+    let mut r = Runtime::new();
+    r.capture_output();
+    r.set_args(vec![String::from("-v"), String::from("in.txt")]);
+
+    r.run(r#"
+set_env("PROJECT_TEST_VAR", "on");
+displn(args(), env("PROJECT_TEST_VAR"), env("PROJECT_TEST_UNSET"));
+"#).unwrap();
+    assert_eq!(r.take_output(), "[-v,in.txt,]\non\nnil\n");
+
+    // exit is not an error a script can catch
+    let e = r.run("try exit(3) catch (e) displn(\"caught\"); displn(\"after\");").unwrap_err();
+    assert_eq!((e.kind, e.exit_code()), (ErrorKind::Exit, 3));
+    assert_eq!(r.take_output(), "");
+
+    assert_eq!(r.run("exit();").unwrap_err().exit_code(), 0);
+    assert_eq!(r.run("1 / 0;").unwrap_err().exit_code(), 1);
+}
+
+#[test]
+fn test_undefined_function() -> () {
+    let mut r = Runtime::new();
+    r.capture_output();
+
+    r.run("fn greet(name) { displn(name) }; struct Point { x, y };").unwrap();
+
+    let e = r.run("gret(1);").unwrap_err();
+    assert_eq!((e.kind, e.message.as_str()), (ErrorKind::Name, "call to undefined function gret, did you mean greet?"));
+    assert_eq!(r.run("Pont(1, 2);").unwrap_err().message, "call to undefined function Pont, did you mean Point?");
+    assert_eq!(r.run("lenn($[1]);").unwrap_err().message, "call to undefined function lenn, did you mean len?");
+    assert_eq!(r.run("frobnicate();").unwrap_err().message, "call to undefined function frobnicate");
+
+    // a caught call from inside a function leaves no frame behind
+    r.run("fn f(x) { try missing(x) catch (e) 0 }; fn g(n) { n > 0 : [f(n); g(n - 1);], 0 }; g(50); y = 1;").unwrap();
+    assert_eq!(r.environment().envs.len(), 1);
+    assert_eq!(r.get_global::<i64>("y"), Some(1));
+}
@@ -1,11 +1,15 @@
 #![allow(unused)]
 
-use std::cmp::min;
+use std::cmp::{min, Ordering};
 use rug::ops::Pow;
 use rug::Float;
 use rug::Integer;
+use indexmap::IndexSet;
+use std::hash::{Hash, Hasher};
+use std::collections::hash_map::DefaultHasher;
+
+use super::error::{ErrorKind, RuntimeError};
 
-use super::error::error;
 
 pub const FLOATING_PRECISION : u32 = 363;
 
@@ -16,9 +20,145 @@ pub enum Value {
     Bool        (bool),
     String      (String),
     List        (Vec<Value>),
+    /// a caught error, see `try`
+    Error       (Box<RuntimeError>),
+    Struct      (Box<Struct>),
+    /// iterates in insertion order
+    Set         (Box<IndexSet<Value>>),
+    /// a function, builtin or struct referred to by name, see `call_value`
+    Function    (String),
     Nil,
 }
 
+/// `==` is structural: lists, structs and errors are equal when their contents are. \
+/// integers and floats compare by value (`1 == 1.0`), any other two values of different types are unequal
+impl PartialEq for Value {
+    fn eq(&self, other : &Value) -> bool {
+        match (self, other) {
+            (Value::Integer(a), Value::Integer(b)) => a == b,
+            (Value::Float(a), Value::Float(b)) => a == b,
+            (Value::Integer(a), Value::Float(b)) | (Value::Float(b), Value::Integer(a)) => b == a,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::String(a), Value::String(b)) => a == b,
+            (Value::List(a), Value::List(b)) => a == b,
+            (Value::Set(a), Value::Set(b)) => a == b,
+            (Value::Function(a), Value::Function(b)) => a == b,
+            (Value::Struct(a), Value::Struct(b)) => a.name == b.name && a.fields == b.fields,
+            (Value::Error(a), Value::Error(b)) => a.kind == b.kind && a.message == b.message && a.value == b.value,
+            (Value::Nil, Value::Nil) => true,
+            _ => false,
+        }
+    }
+}
+
+/// numbers are ordered by value, bools false before true, strings by code points and lists
+/// lexicographically by their elements. \
+/// values of different types (other than int and float), structs and errors are not ordered,
+/// `<` and friends raise a type error for them
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other : &Value) -> Option<Ordering> {
+        match (self, other) {
+            (Value::Integer(a), Value::Integer(b)) => Some(a.cmp(b)),
+            (Value::Float(a), Value::Float(b)) => a.partial_cmp(b),
+            (Value::Integer(a), Value::Float(b)) => a.partial_cmp(b),
+            (Value::Float(a), Value::Integer(b)) => a.partial_cmp(b),
+            (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
+            (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+            (Value::List(a), Value::List(b)) => {
+                for (x, y) in a.iter().zip(b) {
+                    match x.partial_cmp(y)? {
+                        Ordering::Equal => {},
+                        o => return Some(o),
+                    }
+                }
+                Some(a.len().cmp(&b.len()))
+            },
+            (Value::Nil, Value::Nil) => Some(Ordering::Equal),
+            // sets are ordered by inclusion
+            (Value::Set(a), Value::Set(b)) => match (a.is_subset(&**b), b.is_subset(&**a)) {
+                (true, true) => Some(Ordering::Equal),
+                (true, false) => Some(Ordering::Less),
+                (false, true) => Some(Ordering::Greater),
+                (false, false) => None,
+            },
+            _ if self == other => Some(Ordering::Equal),
+            _ => None,
+        }
+    }
+}
+
+/// NaN is the only value not equal to itself, sets refuse it (see `hashable`)
+impl Eq for Value {}
+
+/// consistent with `==`: a float with an integral value hashes like the integer it equals,
+/// and a set hashes the same whatever its order
+impl Hash for Value {
+    fn hash<H : Hasher>(&self, state : &mut H) -> () {
+        match self {
+            Value::Integer(i) => {
+                0u8.hash(state);
+                i.hash(state);
+            },
+            Value::Float(f) => match f.to_integer() {
+                Some(i) if f.is_integer() => {
+                    0u8.hash(state);
+                    i.hash(state);
+                },
+                _ => {
+                    1u8.hash(state);
+                    f.to_f64().to_bits().hash(state);
+                },
+            },
+            Value::Bool(b) => {
+                2u8.hash(state);
+                b.hash(state);
+            },
+            Value::String(s) => {
+                3u8.hash(state);
+                s.hash(state);
+            },
+            Value::List(l) => {
+                4u8.hash(state);
+                l.hash(state);
+            },
+            Value::Set(s) => {
+                5u8.hash(state);
+                s.len().hash(state);
+                let mut sum : u64 = 0;
+                for v in s.iter() {
+                    let mut h = DefaultHasher::new();
+                    v.hash(&mut h);
+                    sum = sum.wrapping_add(h.finish());
+                }
+                sum.hash(state);
+            },
+            Value::Struct(s) => {
+                6u8.hash(state);
+                s.name.hash(state);
+                s.fields.hash(state);
+            },
+            Value::Error(e) => {
+                7u8.hash(state);
+                e.kind.hash(state);
+                e.message.hash(state);
+            },
+            Value::Function(name) => {
+                9u8.hash(state);
+                name.hash(state);
+            },
+            Value::Nil => 8u8.hash(state),
+        }
+    }
+}
+
+/// an instance of a `struct` declaration
+#[derive(Debug, Clone)]
+pub struct Struct {
+    pub name : String,
+    /// in declaration order
+    pub fields : Vec<(String, Value)>,
+}
+
 impl std::fmt::Display for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -35,6 +175,20 @@ impl std::fmt::Display for Value {
                 for e in l { write!(f, "{},", e)?; }
                 write!(f, "]")
             },
+            Value::Error(e) => write!(f, "{}: {}", e.kind.name(), e.message),
+            Value::Function(name) => write!(f, "<fn {}>", name),
+            Value::Set(l) => {
+                write!(f, "${{")?;
+                for e in l.iter() { write!(f, "{},", e)?; }
+                write!(f, "}}")
+            },
+            Value::Struct(s) => {
+                write!(f, "{} {{", s.name)?;
+                for (i, (k, v)) in s.fields.iter().enumerate() {
+                    write!(f, "{} {} : {}", if i == 0 {""} else {","}, k, v)?;
+                }
+                write!(f, " }}")
+            },
             Value::Nil => write!(f, "nil"),
         }
         // write!(f, "({}, {})", self.longitude, self.latitude)
@@ -52,6 +206,10 @@ impl Value {
     fn strength(&self) -> u8 {
         match self {
             Value::Nil => 0,
+            Value::Error(_) => 0,
+            Value::Struct(_) => 0,
+            Value::Set(_) => 0,
+            Value::Function(_) => 0,
             Value::List(_) => 1,
             Value::String(_) => 2,
             Value::Float(_) => 3,
@@ -60,6 +218,7 @@ impl Value {
         }
     }
 
+    #[allow(clippy::wrong_self_convention)]
     fn as_strength(self, s_tag : u8) -> Self {
         match s_tag {
             0 => Value::Nil,
@@ -72,6 +231,17 @@ impl Value {
         }
     }
 
+    /// strongest for the operands of `op`, values without arithmetic are a type error instead of becoming nil
+    fn operands(self, other : Value, op : &str) -> Result<(Value, Value), RuntimeError> {
+        for v in [&self, &other] {
+            if matches!(v, Value::Error(_) | Value::Struct(_) | Value::Set(_) | Value::Function(_)) {
+                return Err(RuntimeError::new(ErrorKind::Type, format!("Cannot {} {} and {}", op, self.type_name(), other.type_name())));
+            }
+        }
+
+        Ok(self.strongest(other))
+    }
+
     /// convert both to the 'strongest type'\
     /// Strongest to Weakest (Nil is the Strongest): \
     ///     Nil \
@@ -118,6 +288,10 @@ impl Value {
                 return Value::Nil;
             },
             Value::List(..) => Value::Nil,
+            Value::Error(..) => Value::Nil,
+            Value::Struct(..) => Value::Nil,
+            Value::Set(..) => Value::Nil,
+            Value::Function(..) => Value::Nil,
             Value::Nil => Value::Nil,
         }
     }
@@ -130,6 +304,10 @@ impl Value {
             Value::Bool(i) => Value::Integer(if i {Integer::from(1)} else {Integer::from(0)}),
             Value::String(_) => Value::Nil,
             Value::List(_) => Value::Nil,
+            Value::Error(_) => Value::Nil,
+            Value::Struct(_) => Value::Nil,
+            Value::Set(_) => Value::Nil,
+            Value::Function(_) => Value::Nil,
             Value::Nil => Value::Nil,
         }
     }
@@ -140,8 +318,12 @@ impl Value {
             Value::Float(f) => Value::Bool(f > 0),
             Value::Integer(i) => Value::Bool(i > 0),
             Value::Bool(..) => self,
-            Value::String(s) => Value::Bool(s.len() > 0),
-            Value::List(l) => Value::Bool(l.len() > 0),
+            Value::String(s) => Value::Bool(!s.is_empty()),
+            Value::List(l) => Value::Bool(!l.is_empty()),
+            Value::Error(_) => Value::Nil,
+            Value::Struct(_) => Value::Bool(true),
+            Value::Set(s) => Value::Bool(!s.is_empty()),
+            Value::Function(_) => Value::Bool(true),
             Value::Nil => Value::Nil,
         }
     }
@@ -154,6 +336,7 @@ impl Value {
             Value::Bool(f) => Value::String(format!("{}", f)),
             Value::String(_) => self,
             Value::List(f) => Value::String(format!("{:?}", f)),
+            Value::Error(_) | Value::Struct(_) | Value::Set(_) | Value::Function(_) => Value::String(format!("{}", self)),
             Value::Nil => Value::Nil,
         }
     }
@@ -162,49 +345,174 @@ impl Value {
     pub fn as_lst(self) -> Value {
         match self {
             Value::List(_) => self,
+            Value::Set(s) => Value::List(s.into_iter().collect()),
             Value::Nil => Value::Nil,
             _ => Value::List(vec![self]),
         }
     }
 
-    pub fn force_bool(self) -> bool {
-        match self.as_bool() {
-            Value::Bool(x) => x,
-            _ => {
-                error(format!("Attempts to convert NILL to boolean"));
-                false
-            }
+    /// `value.name`
+    pub fn field(self, name : &String) -> Result<Value, RuntimeError> {
+        match (self, name.as_str()) {
+            (Value::Error(e), "kind") => Ok(Value::String(String::from(e.kind.name()))),
+            (Value::Error(e), "message") => Ok(Value::String(e.message)),
+            (Value::Error(e), "trace") => Ok(Value::List(e.trace.iter().rev().map(|f| Value::String(format!("{}", f))).collect())),
+            (Value::Error(e), "value") => Ok(match e.value {
+                Some(v) => *v,
+                None => Value::Nil,
+            }),
+            (Value::Struct(s), _) => match s.fields.into_iter().find(|(k, _)| k == name) {
+                Some((_, v)) => Ok(v),
+                None => Err(RuntimeError::new(ErrorKind::Name, format!("{} has no field {}", s.name, name))),
+            },
+            (v, _) => Err(RuntimeError::new(ErrorKind::Type, format!("{} has no field {}", v, name))),
         }
     }
 
-    pub fn is_vec(&self) -> bool {
-        match self {
-            Value::List(_) => true,
-            _ => false,
+    /// `value.name = v`, only the existing fields of a struct can be assigned
+    pub fn set_field(&mut self, name : &String, v : Value) -> Result<(), RuntimeError> {
+        let Value::Struct(s) = self else {
+            return Err(RuntimeError::new(ErrorKind::Type, format!("cannot assign field {} of {}", name, self)));
+        };
+
+        match s.fields.iter_mut().find(|(k, _)| k == name) {
+            Some((_, slot)) => {
+                *slot = v;
+                Ok(())
+            },
+            None => Err(RuntimeError::new(ErrorKind::Name, format!("{} has no field {}", s.name, name))),
         }
     }
 
-    pub fn is_nill(&self) -> bool {
-        match self {
-            Value::Nil => true,
-            _ => false,
+    /// `value[i]`, the i-th element of a list or character of a string
+    pub fn index(&self, i : &Value) -> Result<Value, RuntimeError> {
+        let n = as_index(i)?;
+
+        let v = match self {
+            Value::List(l) => l.get(n).cloned(),
+            Value::String(s) => s.chars().nth(n).map(|c| Value::String(String::from(c))),
+            _ => return Err(RuntimeError::new(ErrorKind::Type, format!("{} can not be indexed", self))),
+        };
+
+        match v {
+            Some(v) => Ok(v),
+            None => Err(RuntimeError::new(ErrorKind::Index, format!("index {} out of bounds", i))),
         }
     }
 
-    pub fn is_integer(&self) -> bool {
-        match self {
-            Value::Integer(_) => true,
-            _ => false,
+    /// `value[i] = v`, only list elements can be assigned
+    pub fn set_index(&mut self, i : &Value, v : Value) -> Result<(), RuntimeError> {
+        let n = as_index(i)?;
+
+        let Value::List(l) = self else {
+            return Err(RuntimeError::new(ErrorKind::Type, format!("cannot assign an element of {}", self)));
+        };
+
+        match l.get_mut(n) {
+            Some(slot) => {
+                *slot = v;
+                Ok(())
+            },
+            None => Err(RuntimeError::new(ErrorKind::Index, format!("index {} out of bounds", i))),
         }
     }
 
-    pub fn is_float(&self) -> bool {
+    /// everything but NaN (which is not equal to itself) can be put in a set
+    pub fn hashable(&self) -> bool {
         match self {
-            Value::Float(_) => true,
-            _ => false,
+            Value::Float(f) => !f.is_nan(),
+            Value::List(l) => l.iter().all(|v| v.hashable()),
+            Value::Struct(s) => s.fields.iter().all(|(_, v)| v.hashable()),
+            Value::Error(e) => e.value.as_ref().is_none_or(|v| v.hashable()),
+            _ => true,
+        }
+    }
+
+    /// a set of the given elements, duplicates are dropped and the first occurrence keeps its place
+    pub fn set_of(items : Vec<Value>) -> Result<Value, RuntimeError> {
+        let mut s = IndexSet::with_capacity(items.len());
+
+        for v in items {
+            if !v.hashable() {
+                return Err(RuntimeError::new(ErrorKind::Type, format!("{} can not be put in a set", v)));
+            }
+            s.insert(v);
+        }
+
+        Ok(Value::Set(Box::new(s)))
+    }
+
+    /// `a | b`, the elements of a followed by the new ones of b
+    pub fn union(self, other : Value) -> Result<Value, RuntimeError> {
+        match (self, other) {
+            (Value::Set(mut a), Value::Set(b)) => {
+                a.extend(*b);
+                Ok(Value::Set(a))
+            },
+            (a, b) => Err(RuntimeError::new(ErrorKind::Type, format!("Cannot take the union of {} and {}", a.type_name(), b.type_name()))),
         }
     }
 
+    /// `a & b`, in the order of a
+    pub fn intersection(self, other : Value) -> Result<Value, RuntimeError> {
+        match (self, other) {
+            (Value::Set(mut a), Value::Set(b)) => {
+                a.retain(|v| b.contains(v));
+                Ok(Value::Set(a))
+            },
+            (a, b) => Err(RuntimeError::new(ErrorKind::Type, format!("Cannot take the intersection of {} and {}", a.type_name(), b.type_name()))),
+        }
+    }
+
+    /// `x in c` for sets, lists and substrings
+    pub fn contains(&self, x : &Value) -> Result<bool, RuntimeError> {
+        match (self, x) {
+            (Value::Set(s), _) => Ok(s.contains(x)),
+            (Value::List(l), _) => Ok(l.contains(x)),
+            (Value::String(s), Value::String(sub)) => Ok(s.contains(sub.as_str())),
+            _ => Err(RuntimeError::new(ErrorKind::Type, format!("Cannot test whether a {} is in a {}", x.type_name(), self.type_name()))),
+        }
+    }
+
+    /// the name reported by `type_of`, structs report their own name
+    pub fn type_name(&self) -> String {
+        String::from(match self {
+            Value::Float(_) => "float",
+            Value::Integer(_) => "int",
+            Value::Bool(_) => "bool",
+            Value::String(_) => "string",
+            Value::List(_) => "list",
+            Value::Error(_) => "error",
+            Value::Set(_) => "set",
+            Value::Function(_) => "function",
+            Value::Struct(s) => return s.name.clone(),
+            Value::Nil => "nil",
+        })
+    }
+
+    pub fn force_bool(self) -> Result<bool, RuntimeError> {
+        match self.as_bool() {
+            Value::Bool(x) => Ok(x),
+            _ => Err(RuntimeError::new(ErrorKind::Type, format!("Attempts to convert NILL to boolean"))),
+        }
+    }
+
+    pub fn is_vec(&self) -> bool {
+        matches!(self, Value::List(_))
+    }
+
+    pub fn is_nill(&self) -> bool {
+        matches!(self, Value::Nil)
+    }
+
+    pub fn is_integer(&self) -> bool {
+        matches!(self, Value::Integer(_))
+    }
+
+    pub fn is_float(&self) -> bool {
+        matches!(self, Value::Float(_))
+    }
+
     pub fn force_float(self) -> Option<Float> {
         match self {
             Value::Float(f) => Some(f),
@@ -221,131 +529,304 @@ impl Value {
 
     // self - other \
     // (rounded to -1, 0, or 1) \
-    // returns None if conversion error
+    // returns None if the values are not ordered, see PartialOrd
     pub fn comp(self, other : Value) -> Option<i8> {
-        if !(self.is_comparable() && other.is_comparable()) {
-            return None;
-        }
-
-        if self.is_float() || other.is_float() {
-            let Some( one ) = self.as_float().force_float() else {return None;};
-            let Some( two ) = other.as_float().force_float() else {return None;};
-            
-            if one < two {
-                Some(-1)
-            }
-            else if one > two {
-                Some(1)
-            }
-            else {
-                Some(0)
-            }
-        }
-        else {
-            let Some( one ) = self.as_integer().force_integer() else {return None;};
-            let Some( two ) = other.as_integer().force_integer() else {return None;};
-
-            if one < two {
-                Some(-1)
-            }
-            else if one > two {
-                Some(1)
-            }
-            else {
-                Some(0)
-            }
+        match self.partial_cmp(&other)? {
+            Ordering::Less => Some(-1),
+            Ordering::Equal => Some(0),
+            Ordering::Greater => Some(1),
         }
     }
 
     pub fn is_comparable(&self) -> bool {
-        match self {
-            &Value::Float(_) => true,
-            &Value::Integer(_) => true,
-            _ => false,
-        }
+        matches!(self, Value::Float(_) | Value::Integer(_))
     }
 
-    pub fn add(self, other : Value) -> Value {
-        let (l, r) = self.strongest(other);
+    #[allow(clippy::should_implement_trait)]
+    pub fn add(self, other : Value) -> Result<Value, RuntimeError> {
+        let (l, r) = self.operands(other, "add")?;
 
         match (l, r) {
-            (Value::Float(mut f1), Value::Float(f2)) => {f1 += f2; Value::Float(f1)},
-            (Value::Integer(mut i1), Value::Integer(mut i2)) => {i1 += i2; Value::Integer(i1)},
-            (Value::Bool(b1), Value::Bool(b2)) => {error(format!("Cannot add bool to bool"))},
-            (Value::String(mut s1), Value::String(s2)) => {s1.extend(s2.chars()); Value::String(s1)},
-            (Value::List(mut l1), Value::List(l2)) => {l1.extend(l2); Value::List(l1)},
-            (Value::Nil, Value::Nil) => Value::Nil,
+            (Value::Float(mut f1), Value::Float(f2)) => {f1 += f2; Ok(Value::Float(f1))},
+            (Value::Integer(mut i1), Value::Integer(mut i2)) => {i1 += i2; Ok(Value::Integer(i1))},
+            (Value::Bool(b1), Value::Bool(b2)) => Err(RuntimeError::new(ErrorKind::Type, format!("Cannot add bool to bool"))),
+            (Value::String(mut s1), Value::String(s2)) => {s1.push_str(&s2); Ok(Value::String(s1))},
+            (Value::List(mut l1), Value::List(l2)) => {l1.extend(l2); Ok(Value::List(l1))},
+            (Value::Nil, Value::Nil) => Ok(Value::Nil),
             (_, _)  => unreachable!(),
         }
     }
 
-    pub fn sub(self, other : Value) -> Value {
-        let (l, r) = self.strongest(other);
+    #[allow(clippy::should_implement_trait)]
+    pub fn sub(self, other : Value) -> Result<Value, RuntimeError> {
+        // set difference, in the order of the left set
+        if let (Value::Set(a), Value::Set(b)) = (&self, &other) {
+            return Ok(Value::Set(Box::new(a.iter().filter(|v| !b.contains(*v)).cloned().collect())));
+        }
+
+        let (l, r) = self.operands(other, "sub")?;
 
         match (l, r) {
-            (Value::Float(mut f1), Value::Float(f2)) => {f1 -= f2; Value::Float(f1)},
-            (Value::Integer(mut i1), Value::Integer(mut i2)) => {i1 -= i2; Value::Integer(i1)},
-            (Value::Bool(b1), Value::Bool(b2)) => error(format!("Cannot sub bool from bool")),
-            (Value::String(s1), Value::String(s2)) => error(format!("Cannot sub string")),
-            (Value::List(l1), Value::List(l2)) => error(format!("Cannot sub list")),
-            (Value::Nil, Value::Nil) => Value::Nil,
+            (Value::Float(mut f1), Value::Float(f2)) => {f1 -= f2; Ok(Value::Float(f1))},
+            (Value::Integer(mut i1), Value::Integer(mut i2)) => {i1 -= i2; Ok(Value::Integer(i1))},
+            (Value::Bool(b1), Value::Bool(b2)) => Err(RuntimeError::new(ErrorKind::Type, format!("Cannot sub bool from bool"))),
+            (Value::String(s1), Value::String(s2)) => Err(RuntimeError::new(ErrorKind::Type, format!("Cannot sub string"))),
+            (Value::List(l1), Value::List(l2)) => Err(RuntimeError::new(ErrorKind::Type, format!("Cannot sub list"))),
+            (Value::Nil, Value::Nil) => Ok(Value::Nil),
             (_, _)  => unreachable!(),
         }
     }
 
-    pub fn mul(self, other : Value) -> Value {
-        let (l, r) = self.strongest(other);
+    #[allow(clippy::should_implement_trait)]
+    pub fn mul(self, other : Value) -> Result<Value, RuntimeError> {
+        let (l, r) = self.operands(other, "mul")?;
 
         match (l, r) {
-            (Value::Float(mut f1), Value::Float(f2)) => {f1 *= f2; Value::Float(f1)},
-            (Value::Integer(mut i1), Value::Integer(mut i2)) => {i1 *= i2; Value::Integer(i1)},
-            (Value::Bool(b1), Value::Bool(b2)) => error(format!("Cannot mul bool and bool")),
-            (Value::String(s1), Value::String(s2)) => error(format!("Cannot mul string")),
-            (Value::List(l1), Value::List(l2)) => error(format!("Cannot mul list")),
-            (Value::Nil, Value::Nil) => Value::Nil,
+            (Value::Float(mut f1), Value::Float(f2)) => {f1 *= f2; Ok(Value::Float(f1))},
+            (Value::Integer(mut i1), Value::Integer(mut i2)) => {i1 *= i2; Ok(Value::Integer(i1))},
+            (Value::Bool(b1), Value::Bool(b2)) => Err(RuntimeError::new(ErrorKind::Type, format!("Cannot mul bool and bool"))),
+            (Value::String(s1), Value::String(s2)) => Err(RuntimeError::new(ErrorKind::Type, format!("Cannot mul string"))),
+            (Value::List(l1), Value::List(l2)) => Err(RuntimeError::new(ErrorKind::Type, format!("Cannot mul list"))),
+            (Value::Nil, Value::Nil) => Ok(Value::Nil),
             (_, _)  => unreachable!(),
         }
     }
 
-    pub fn div(self, other : Value) -> Value {
-        let (l, r) = self.strongest(other);
+    #[allow(clippy::should_implement_trait)]
+    pub fn div(self, other : Value) -> Result<Value, RuntimeError> {
+        let (l, r) = self.operands(other, "div")?;
 
         match (l, r) {
-            (Value::Float(mut f1), Value::Float(f2)) => {if f2 == 0 {error(format!("division by zero!"))} else {f1 /= f2; Value::Float(f1)}},
-            (Value::Integer(mut i1), Value::Integer(mut i2)) => {if i2 == 0 {return error(format!("division by zero!"));}; i1 /= i2; Value::Integer(i1)},
-            (Value::Bool(b1), Value::Bool(b2)) => error(format!("Cannot div bool and bool")),
-            (Value::String(s1), Value::String(s2)) => error(format!("Cannot div (by) string")),
-            (Value::List(l1), Value::List(l2)) => error(format!("Cannot div (by) list")),
-            (Value::Nil, Value::Nil) => Value::Nil,
+            (Value::Float(mut f1), Value::Float(f2)) => {if f2 == 0 {Err(RuntimeError::new(ErrorKind::DivisionByZero, format!("division by zero!")))} else {f1 /= f2; Ok(Value::Float(f1))}},
+            (Value::Integer(mut i1), Value::Integer(mut i2)) => {if i2 == 0 {return Err(RuntimeError::new(ErrorKind::DivisionByZero, format!("division by zero!")));}; i1 /= i2; Ok(Value::Integer(i1))},
+            (Value::Bool(b1), Value::Bool(b2)) => Err(RuntimeError::new(ErrorKind::Type, format!("Cannot div bool and bool"))),
+            (Value::String(s1), Value::String(s2)) => Err(RuntimeError::new(ErrorKind::Type, format!("Cannot div (by) string"))),
+            (Value::List(l1), Value::List(l2)) => Err(RuntimeError::new(ErrorKind::Type, format!("Cannot div (by) list"))),
+            (Value::Nil, Value::Nil) => Ok(Value::Nil),
             (_, _)  => unreachable!(),
         }
     }
 
-    pub fn pow(self, other : Value) -> Value {
-        let (l, r) = self.strongest(other);
+    pub fn pow(self, other : Value) -> Result<Value, RuntimeError> {
+        let (l, r) = self.operands(other, "raise")?;
 
         match (l, r) {
-            (Value::Float(mut f1), Value::Float(f2)) => {f1 = f1.pow(f2); Value::Float(f1)},
-            (Value::Integer(mut i1), Value::Integer(mut i2)) => {let t = i2.to_u32(); if t.is_none() {return error(format!("Exponent too big! (Or Small). To bypass this error first convert to float using float(arg)."));}; i1 = i1.pow(t.unwrap()); Value::Integer(i1)},
-            (Value::Bool(b1), Value::Bool(b2)) => error(format!("Cannot raise bool to power")),
-            (Value::String(s1), Value::String(s2)) => error(format!("Cannot raise String to Value or raise Value to String")),
-            (Value::List(l1), Value::List(l2)) => error(format!("Cannot raise List to Value or raise Value to List")),
-            (Value::Nil, Value::Nil) => Value::Nil,
+            (Value::Float(mut f1), Value::Float(f2)) => {f1 = f1.pow(f2); Ok(Value::Float(f1))},
+            (Value::Integer(mut i1), Value::Integer(mut i2)) => {let t = i2.to_u32(); if t.is_none() {return Err(RuntimeError::new(ErrorKind::Type, format!("Exponent too big! (Or Small). To bypass this error first convert to float using float(arg).")));}; i1 = i1.pow(t.unwrap()); Ok(Value::Integer(i1))},
+            (Value::Bool(b1), Value::Bool(b2)) => Err(RuntimeError::new(ErrorKind::Type, format!("Cannot raise bool to power"))),
+            (Value::String(s1), Value::String(s2)) => Err(RuntimeError::new(ErrorKind::Type, format!("Cannot raise String to Value or raise Value to String"))),
+            (Value::List(l1), Value::List(l2)) => Err(RuntimeError::new(ErrorKind::Type, format!("Cannot raise List to Value or raise Value to List"))),
+            (Value::Nil, Value::Nil) => Ok(Value::Nil),
             (_, _)  => unreachable!(),
         }
     }
 
-    pub fn mod_(self, other : Value) -> Value {
-        let (l, r) = self.strongest(other);
+    pub fn mod_(self, other : Value) -> Result<Value, RuntimeError> {
+        let (l, r) = self.operands(other, "mod")?;
 
         match (l, r) {
-            (Value::Float(mut f1), Value::Float(f2)) => {f1 %= f2; Value::Float(f1)},
-            (Value::Integer(mut i1), Value::Integer(mut i2)) => {i1 %= i2; Value::Integer(i1)},
-            (Value::Bool(b1), Value::Bool(b2)) => error(format!("Cannot mod bool and bool")),
-            (Value::String(s1), Value::String(s2)) => error(format!("Cannot mod (by) string")),
-            (Value::List(l1), Value::List(l2)) => error(format!("Cannot mod (by) list")),
-            (Value::Nil, Value::Nil) => Value::Nil,
+            (Value::Float(mut f1), Value::Float(f2)) => {f1 %= f2; Ok(Value::Float(f1))},
+            (Value::Integer(mut i1), Value::Integer(mut i2)) => {if i2 == 0 {return Err(RuntimeError::new(ErrorKind::DivisionByZero, format!("division by zero!")));}; i1 %= i2; Ok(Value::Integer(i1))},
+            (Value::Bool(b1), Value::Bool(b2)) => Err(RuntimeError::new(ErrorKind::Type, format!("Cannot mod bool and bool"))),
+            (Value::String(s1), Value::String(s2)) => Err(RuntimeError::new(ErrorKind::Type, format!("Cannot mod (by) string"))),
+            (Value::List(l1), Value::List(l2)) => Err(RuntimeError::new(ErrorKind::Type, format!("Cannot mod (by) list"))),
+            (Value::Nil, Value::Nil) => Ok(Value::Nil),
             (_, _)  => unreachable!(),
         }
     }
-}
\ No newline at end of file
+}
+
+fn as_index(i : &Value) -> Result<usize, RuntimeError> {
+    match i {
+        Value::Integer(n) => n.to_usize().ok_or_else(|| RuntimeError::new(ErrorKind::Index, format!("index {} out of bounds", n))),
+        _ => Err(RuntimeError::new(ErrorKind::Type, format!("index must be an integer, found {}", i))),
+    }
+}
+
+/// returned when a Value can not be converted into the requested rust type
+#[derive(Debug, Clone)]
+pub struct ConversionError {
+    pub expected : &'static str,
+    pub found : Value,
+}
+
+impl std::fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "expected {}, found {}", self.expected, self.found)
+    }
+}
+
+impl std::error::Error for ConversionError {}
+
+macro_rules! integer_conversions {
+    ($($t:ty => $to:ident),*) => {$(
+        impl From<$t> for Value {
+            fn from(i : $t) -> Value {
+                Value::Integer(Integer::from(i))
+            }
+        }
+
+        impl TryFrom<Value> for $t {
+            type Error = ConversionError;
+
+            fn try_from(v : Value) -> Result<$t, ConversionError> {
+                match &v {
+                    Value::Integer(i) => match i.$to() {
+                        Some(x) => Ok(x),
+                        None => Err(ConversionError { expected : stringify!($t), found : v }),
+                    },
+                    _ => Err(ConversionError { expected : stringify!($t), found : v }),
+                }
+            }
+        }
+    )*};
+}
+
+integer_conversions!(i32 => to_i32, i64 => to_i64, u32 => to_u32, u64 => to_u64, usize => to_usize);
+
+impl From<f64> for Value {
+    fn from(f : f64) -> Value {
+        Value::Float(Float::with_val(FLOATING_PRECISION, f))
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b : bool) -> Value {
+        Value::Bool(b)
+    }
+}
+
+impl From<String> for Value {
+    fn from(s : String) -> Value {
+        Value::String(s)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s : &str) -> Value {
+        Value::String(String::from(s))
+    }
+}
+
+impl From<Integer> for Value {
+    fn from(i : Integer) -> Value {
+        Value::Integer(i)
+    }
+}
+
+impl From<Float> for Value {
+    fn from(f : Float) -> Value {
+        Value::Float(f)
+    }
+}
+
+impl From<()> for Value {
+    fn from(_ : ()) -> Value {
+        Value::Nil
+    }
+}
+
+impl<T : Into<Value>> From<Vec<T>> for Value {
+    fn from(l : Vec<T>) -> Value {
+        Value::List(l.into_iter().map(|x| x.into()).collect())
+    }
+}
+
+impl<T : Into<Value>> From<Option<T>> for Value {
+    fn from(o : Option<T>) -> Value {
+        match o {
+            Some(x) => x.into(),
+            None => Value::Nil,
+        }
+    }
+}
+
+/// integers are widened, every other type is rejected
+impl TryFrom<Value> for f64 {
+    type Error = ConversionError;
+
+    fn try_from(v : Value) -> Result<f64, ConversionError> {
+        match v {
+            Value::Float(f) => Ok(f.to_f64()),
+            Value::Integer(i) => Ok(i.to_f64()),
+            _ => Err(ConversionError { expected : "f64", found : v }),
+        }
+    }
+}
+
+impl TryFrom<Value> for bool {
+    type Error = ConversionError;
+
+    fn try_from(v : Value) -> Result<bool, ConversionError> {
+        match v {
+            Value::Bool(b) => Ok(b),
+            _ => Err(ConversionError { expected : "bool", found : v }),
+        }
+    }
+}
+
+impl TryFrom<Value> for String {
+    type Error = ConversionError;
+
+    fn try_from(v : Value) -> Result<String, ConversionError> {
+        match v {
+            Value::String(s) => Ok(s),
+            _ => Err(ConversionError { expected : "String", found : v }),
+        }
+    }
+}
+
+impl TryFrom<Value> for Integer {
+    type Error = ConversionError;
+
+    fn try_from(v : Value) -> Result<Integer, ConversionError> {
+        match v {
+            Value::Integer(i) => Ok(i),
+            _ => Err(ConversionError { expected : "Integer", found : v }),
+        }
+    }
+}
+
+impl TryFrom<Value> for Float {
+    type Error = ConversionError;
+
+    fn try_from(v : Value) -> Result<Float, ConversionError> {
+        match v {
+            Value::Float(f) => Ok(f),
+            Value::Integer(_) => Ok(v.as_float().force_float().unwrap()),
+            _ => Err(ConversionError { expected : "Float", found : v }),
+        }
+    }
+}
+
+impl TryFrom<Value> for Vec<Value> {
+    type Error = ConversionError;
+
+    fn try_from(v : Value) -> Result<Vec<Value>, ConversionError> {
+        match v {
+            Value::List(l) => Ok(l),
+            _ => Err(ConversionError { expected : "List", found : v }),
+        }
+    }
+}
+
+#[test]
+fn test_comparisons() -> () {
+    let l = |v : Vec<Value>| Value::List(v);
+
+    assert_eq!(Value::from("a"), Value::from("a"));
+    assert_eq!(l(vec![1.into(), "x".into()]), l(vec![1.0.into(), "x".into()]));
+    assert_eq!(Value::Nil, Value::Nil);
+    assert_ne!(Value::from(1), Value::from("1"));
+    assert_ne!(Value::from(true), Value::from(1));
+    assert_ne!(l(vec![1.into()]), l(vec![1.into(), 2.into()]));
+
+    assert!(Value::from("abc") < Value::from("abd"));
+    assert!(Value::from("ab") < Value::from("abc"));
+    assert!(l(vec![1.into(), 5.into()]) < l(vec![2.into()]));
+    assert!(l(vec![1.into()]) < l(vec![1.into(), 0.into()]));
+    assert!(Value::from(2) > Value::from(1.5));
+    assert_eq!(Value::from(1).partial_cmp(&Value::from("1")), None);
+    assert_eq!(l(vec!["a".into()]).partial_cmp(&l(vec![1.into()])), None);
+    assert_eq!(Value::from(3).comp(Value::from(3.0)), Some(0));
+}